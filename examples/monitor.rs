@@ -26,6 +26,7 @@ struct Monitor {
 trait MonitorInteraction {
     async fn ping(&self);
     async fn suspicion(&self) -> f64;
+    #[allow(dead_code)]
     async fn show_history(&self);
     async fn publish_csv(&self, filename: &str);
 }
@@ -62,6 +63,7 @@ impl MonitorInteraction for Monitor {
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(true)
             .open(file_path)
             .unwrap();
         let history = self.history.read().await;
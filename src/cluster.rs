@@ -0,0 +1,93 @@
+use std::hash::Hash;
+use std::sync::Arc;
+use chrono::{DateTime, Local};
+use futures_util::StreamExt;
+use tokio::sync::watch;
+use crate::DetectorRegistry;
+
+/// Quorum watch over the nodes of a DetectorRegistry, e.g. to react once fewer than half of
+/// the nodes are alive
+#[derive(Debug)]
+pub struct ClusterHealth<K> {
+    registry: Arc<DetectorRegistry<K>>,
+    quorum: f64,
+    quorum_held: watch::Sender<bool>,
+}
+
+impl<K> ClusterHealth<K>
+    where K: Eq + Hash + Clone + Send + Sync {
+    /// New ClusterHealth holding quorum while at least the quorum fraction of nodes is available.
+    /// Quorum is assumed held until the first `check`.
+    pub fn new(registry: Arc<DetectorRegistry<K>>, quorum: f64) -> Self {
+        ClusterHealth {
+            registry,
+            quorum,
+            quorum_held: watch::channel(true).0,
+        }
+    }
+
+    /// Watched registry.
+    pub fn registry(&self) -> &Arc<DetectorRegistry<K>> {
+        &self.registry
+    }
+
+    /// Fraction of the nodes whose phi at now is at or below threshold, 1 for an empty registry.
+    pub async fn available_fraction(&self, now: DateTime<Local>, threshold: f64) -> f64 {
+        let (mut available, mut total) = (0, 0);
+        let mut evaluations = Box::pin(Arc::clone(&self.registry).evaluate_all(now, threshold));
+        while let Some((_, evaluation)) = evaluations.next().await {
+            total += 1;
+            if evaluation.available {
+                available += 1;
+            }
+        }
+        if total == 0 { 1. } else { available as f64 / total as f64 }
+    }
+
+    /// Whether quorum holds at now, notifying subscribers when that changed since the last check.
+    pub async fn check(&self, now: DateTime<Local>, threshold: f64) -> bool {
+        let held = self.available_fraction(now, threshold).await >= self.quorum;
+        self.quorum_held.send_if_modified(|current| std::mem::replace(current, held) != held);
+        held
+    }
+
+    /// Receiver of whether quorum holds, marked changed whenever `check` sees it cross.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.quorum_held.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Add;
+    use std::sync::Arc;
+    use chrono::{Duration, Local, TimeDelta};
+    use crate::{ClusterHealth, DetectorConfig, DetectorRegistry};
+
+    #[tokio::test]
+    async fn test_quorum_lost_when_majority_stops() {
+        let registry = Arc::new(DetectorRegistry::new(DetectorConfig { min_std_deviation: TimeDelta::milliseconds(10), ..DetectorConfig::default() }));
+        let cluster = ClusterHealth::new(Arc::clone(&registry), 0.5);
+        let mut quorum = cluster.subscribe();
+        let nodes = ["a", "b", "c", "d", "e"];
+        let start = Local::now();
+        for at in [0, 100, 200, 300] {
+            for node in nodes {
+                registry.heartbeat(node, start.add(Duration::milliseconds(at))).await.unwrap();
+            }
+        }
+        assert!(cluster.check(start.add(Duration::milliseconds(350)), 8.).await);
+        assert!(!quorum.has_changed().unwrap());
+
+        for at in [400, 500, 600] {
+            for node in &nodes[..2] {
+                registry.heartbeat(*node, start.add(Duration::milliseconds(at))).await.unwrap();
+            }
+        }
+        let t = start.add(Duration::milliseconds(650));
+        assert_eq!(0.4, cluster.available_fraction(t, 8.).await);
+        assert!(!cluster.check(t, 8.).await);
+        assert!(quorum.has_changed().unwrap());
+        assert!(!*quorum.borrow_and_update());
+    }
+}
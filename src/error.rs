@@ -0,0 +1,102 @@
+use std::error::Error;
+use std::fmt;
+
+/// Errors raised by the detector
+#[derive(Clone, Debug, PartialEq)]
+pub enum PhiError {
+    /// Arrival at index is earlier than the one before it.
+    UnorderedArrivals { index: usize },
+    /// The window holds no intervals.
+    EmptyWindow,
+    /// Percentile outside of [0, 100].
+    InvalidPercentile(f64),
+    /// Quantile outside of [0, 1].
+    InvalidQuantile(f64),
+    /// A configuration tunable is out of range.
+    InvalidConfig(String),
+    /// Fewer intervals are available than required.
+    InsufficientSamples { available: usize, required: usize },
+    /// No window exists at the index.
+    InvalidWindowIndex(usize),
+    /// Heartbeat from an incarnation older than the current one.
+    StaleIncarnation { current: u64, received: u64 },
+    /// The configuration lock was poisoned by a panic and `PoisonPolicy::Fail` is configured.
+    Poisoned,
+    /// No heartbeat has arrived yet.
+    NoHeartbeat,
+    /// Interval since the last accepted heartbeat exceeds the configured maximum.
+    IntervalOverflow { interval_ms: i64, max_ms: i64 },
+}
+
+impl fmt::Display for PhiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhiError::UnorderedArrivals { index } => {
+                write!(f, "arrival at index {} is earlier than the previous arrival", index)
+            }
+            PhiError::EmptyWindow => write!(f, "the window holds no intervals"),
+            PhiError::InvalidPercentile(p) => write!(f, "percentile {} is outside of [0, 100]", p),
+            PhiError::InvalidQuantile(q) => write!(f, "quantile {} is outside of [0, 1]", q),
+            PhiError::InvalidConfig(reason) => write!(f, "invalid configuration: {}", reason),
+            PhiError::InsufficientSamples { available, required } => {
+                write!(f, "{} intervals available, at least {} required", available, required)
+            }
+            PhiError::InvalidWindowIndex(idx) => write!(f, "no window at index {}", idx),
+            PhiError::StaleIncarnation { current, received } => {
+                write!(f, "heartbeat from incarnation {} is older than the current incarnation {}", received, current)
+            }
+            PhiError::Poisoned => write!(f, "the configuration lock is poisoned"),
+            PhiError::NoHeartbeat => write!(f, "no heartbeat has arrived yet"),
+            PhiError::IntervalOverflow { interval_ms, max_ms } => {
+                write!(f, "interval of {} ms exceeds the maximum of {} ms", interval_ms, max_ms)
+            }
+        }
+    }
+}
+
+impl Error for PhiError {}
+
+/// Errors raised by the non-blocking `try_` variants of the detector
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryError {
+    /// The lock is held and acquiring it would block.
+    WouldBlock,
+    /// The configuration lock is poisoned and `PoisonPolicy::Fail` is configured.
+    Poisoned,
+}
+
+impl fmt::Display for TryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryError::WouldBlock => write!(f, "the detector is locked and the operation would block"),
+            TryError::Poisoned => write!(f, "the configuration lock is poisoned"),
+        }
+    }
+}
+
+impl Error for TryError {}
+
+/// Errors raised by a `NodeStore`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StoreError {
+    /// The stored version differs from the expected one, another writer got there first.
+    VersionConflict { expected: u64, actual: u64 },
+    /// The store backend failed.
+    Backend(String),
+    /// The configuration lock of the detector is poisoned and `PoisonPolicy::Fail` is configured.
+    Poisoned,
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::VersionConflict { expected, actual } => {
+                write!(f, "expected version {} but the store holds version {}", expected, actual)
+            }
+            StoreError::Backend(reason) => write!(f, "store backend failed: {}", reason),
+            StoreError::Poisoned => write!(f, "the configuration lock is poisoned"),
+        }
+    }
+}
+
+impl Error for StoreError {}
@@ -0,0 +1,81 @@
+use std::error::Error;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use crate::{Detector, Statistics};
+
+/// Series of a Grafana SimpleJSON `/query` response, with datapoints as (value, unix ms) pairs
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TimeSeries {
+    pub target: String,
+    pub datapoints: Vec<(f64, i64)>,
+}
+
+/// Grafana SimpleJSON `/query` response, serialized as an array of series
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct SimpleJsonResponse(pub Vec<TimeSeries>);
+
+impl Detector {
+    /// "phi", "mean" and "std_dev" series for the heartbeats in the arrival log that were accepted
+    /// within [from, to]. Phi is taken right before each heartbeat, mean and standard deviation
+    /// (in ms) right after it, replaying the log from its start. Empty unless the arrival log is enabled.
+    pub async fn simple_json(&self, from: DateTime<Local>, to: DateTime<Local>) -> Result<SimpleJsonResponse, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let log = self.arrival_log().await;
+        let mut replayed = Statistics::new(config.window_length);
+        replayed.configure(&config);
+        let (mut phi, mut mean, mut std_dev) = (vec![], vec![], vec![]);
+        for entry in log.iter().filter(|entry| entry.rejection.is_none()) {
+            let at = entry.arrived_at;
+            let before = (replayed.n != 0).then(|| replayed.phi_at(at, &config));
+            replayed.insert(at);
+            if at < from || at > to {
+                continue;
+            }
+            let ms = at.timestamp_millis();
+            if let Some(value) = before {
+                phi.push((value, ms));
+            }
+            if !replayed.arrival_intervals.is_empty() {
+                let (variance, mu) = replayed.moments(config.variance_reference);
+                mean.push((mu, ms));
+                std_dev.push((variance.sqrt(), ms));
+            }
+        }
+        Ok(SimpleJsonResponse(vec![
+            TimeSeries { target: "phi".to_string(), datapoints: phi },
+            TimeSeries { target: "mean".to_string(), datapoints: mean },
+            TimeSeries { target: "std_dev".to_string(), datapoints: std_dev },
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Add;
+    use chrono::{Duration, Local, TimeDelta};
+    use serde_json::json;
+    use crate::{DetectorBuilder, PhiInteraction};
+
+    #[tokio::test]
+    async fn test_simple_json_shape() {
+        let builder = || DetectorBuilder::new().window_length(10).min_std_deviation(TimeDelta::milliseconds(10));
+        let detector = builder().with_arrival_log(10).build();
+        let reference = builder().build();
+        let start = Local::now();
+        let at = |ms: i64| start.add(Duration::milliseconds(ms));
+        let mut phis = vec![];
+        for ms in [0, 100, 300] {
+            phis.push(reference.phi(at(ms)).await.unwrap());
+            reference.insert(at(ms)).await.unwrap();
+            detector.insert(at(ms)).await.unwrap();
+        }
+        let response = detector.simple_json(at(50), at(300)).await.unwrap();
+        let ms = |ms: i64| at(ms).timestamp_millis();
+        assert_eq!(json!([
+            { "target": "phi", "datapoints": [[phis[1], ms(100)], [phis[2], ms(300)]] },
+            { "target": "mean", "datapoints": [[100.0, ms(100)], [150.0, ms(300)]] },
+            { "target": "std_dev", "datapoints": [[0.0, ms(100)], [50.0, ms(300)]] },
+        ]), serde_json::to_value(&response).unwrap());
+    }
+}
@@ -0,0 +1,74 @@
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use crate::InstantDetector;
+
+/// Detector fed by the process itself, for answering "am I healthy?" in pull-based health checks.
+/// A spawned task heartbeats on a timer, so phi rises once the runtime or the timer stalls.
+#[derive(Debug)]
+pub struct SelfHeartbeat {
+    detector: Arc<InstantDetector>,
+    task: JoinHandle<()>,
+}
+
+impl SelfHeartbeat {
+    /// Spawn a task inserting a heartbeat at tokio's current instant every period until token is
+    /// cancelled or the SelfHeartbeat is dropped.
+    pub fn spawn(detector: InstantDetector, period: StdDuration, token: CancellationToken) -> Self {
+        let detector = Arc::new(detector);
+        let beating = Arc::clone(&detector);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = ticker.tick() => {
+                        let _ = beating.insert_now().await;
+                    }
+                }
+            }
+        });
+        SelfHeartbeat { detector, task }
+    }
+
+    /// Detector the heartbeats are inserted into.
+    pub fn detector(&self) -> &InstantDetector {
+        &self.detector
+    }
+
+    /// Whether phi at tokio's current instant is at or below threshold.
+    pub async fn am_i_healthy(&self, threshold: f64) -> Result<bool, Box<dyn Error>> {
+        Ok(self.detector.phi_now().await? <= threshold)
+    }
+}
+
+impl Drop for SelfHeartbeat {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use chrono::TimeDelta;
+    use tokio_util::sync::CancellationToken;
+    use crate::{DetectorBuilder, InstantDetector, SelfHeartbeat};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stalled_self_heartbeat_degrades_health() {
+        let detector = InstantDetector::new(DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build());
+        let token = CancellationToken::new();
+        let heartbeat = SelfHeartbeat::spawn(detector, Duration::from_millis(100), token.clone());
+        for _ in 0..20 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(heartbeat.am_i_healthy(8.).await.unwrap());
+
+        token.cancel();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!heartbeat.am_i_healthy(8.).await.unwrap());
+    }
+}
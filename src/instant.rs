@@ -0,0 +1,106 @@
+use std::error::Error;
+use chrono::{DateTime, Local, TimeDelta};
+use tokio::time::Instant;
+use crate::{Detector, PhiInteraction};
+
+/// Detector driven by `tokio::time::Instant`, so it follows tokio's paused and auto-advancing clocks.
+/// Instants are mapped onto the wall-clock timeline of the wrapped Detector through a fixed anchor.
+#[derive(Debug)]
+pub struct InstantDetector {
+    detector: Detector,
+    anchor: ClockAnchor,
+}
+
+/// Tokio instant pinned to the wall-clock time it was taken at, mapping between both timelines
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ClockAnchor {
+    instant: Instant,
+    at: DateTime<Local>,
+}
+
+impl ClockAnchor {
+    /// Anchor tokio's current instant to the current wall-clock time.
+    pub(crate) fn now() -> Self {
+        ClockAnchor { instant: Instant::now(), at: Local::now() }
+    }
+
+    /// Wall-clock time of instant t.
+    pub(crate) fn to_date_time(self, t: Instant) -> DateTime<Local> {
+        let offset = |d: std::time::Duration| TimeDelta::from_std(d).unwrap_or(TimeDelta::MAX);
+        if t >= self.instant {
+            self.at + offset(t - self.instant)
+        } else {
+            self.at - offset(self.instant - t)
+        }
+    }
+
+    /// Instant of wall-clock time at, the anchor itself for times before it.
+    pub(crate) fn to_instant(self, at: DateTime<Local>) -> Instant {
+        self.instant + (at - self.at).to_std().unwrap_or_default()
+    }
+}
+
+impl InstantDetector {
+    /// New InstantDetector anchoring tokio's current instant to the current wall-clock time.
+    pub fn new(detector: Detector) -> Self {
+        InstantDetector {
+            detector,
+            anchor: ClockAnchor::now(),
+        }
+    }
+
+    /// Wrapped Detector.
+    pub fn detector(&self) -> &Detector {
+        &self.detector
+    }
+
+    /// Insertion of heartbeat arrival instant.
+    pub async fn insert(&self, arrived_at: Instant) -> Result<(), Box<dyn Error>> {
+        self.detector.insert(self.to_date_time(arrived_at)).await
+    }
+
+    /// Insertion of a heartbeat arriving at tokio's current instant.
+    pub async fn insert_now(&self) -> Result<(), Box<dyn Error>> {
+        self.insert(Instant::now()).await
+    }
+
+    /// Phi at instant t.
+    pub async fn phi(&self, t: Instant) -> Result<f64, Box<dyn Error>> {
+        self.detector.phi(self.to_date_time(t)).await
+    }
+
+    /// Phi at tokio's current instant.
+    pub async fn phi_now(&self) -> Result<f64, Box<dyn Error>> {
+        self.phi(Instant::now()).await
+    }
+
+    fn to_date_time(&self, t: Instant) -> DateTime<Local> {
+        self.anchor.to_date_time(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use chrono::TimeDelta;
+    use crate::{DetectorBuilder, InstantDetector};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_phi_grows_with_advanced_paused_time() {
+        let detector = InstantDetector::new(DetectorBuilder::new()
+            .window_length(10)
+            .min_std_deviation(TimeDelta::milliseconds(10))
+            .build());
+        for _ in 0..10 {
+            detector.insert_now().await.unwrap();
+            tokio::time::advance(Duration::from_millis(100)).await;
+        }
+        let mut previous = detector.phi_now().await.unwrap();
+        for _ in 0..5 {
+            tokio::time::advance(Duration::from_millis(10)).await;
+            let phi = detector.phi_now().await.unwrap();
+            assert!(phi > previous);
+            previous = phi;
+        }
+    }
+}
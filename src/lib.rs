@@ -37,10 +37,8 @@
 //!    }
 //!}
 //!
-//! fn main() {
-//!   let detector = Arc::new(Detector::new(1000));
-//!   let monitor = Monitor { detector: Arc::clone(&detector) };
-//! }
+//! let detector = Arc::new(Detector::new(1000));
+//! let monitor = Monitor { detector: Arc::clone(&detector) };
 //! ```
 //!
 //! The above example gives you an implementation of a Monitor struct which can be used to interact
@@ -61,237 +59,4759 @@
 //!
 //! // implementation and traits remain the same.
 //!
-//! fn main() {
-//!   let detector = Arc::new(Detector::with_acceptable_pause(1000, TimeDelta::milliseconds(1000)));
-//!   let monitor = Monitor { detector: Arc::clone(&detector) };
-//! }
+//! let detector = Arc::new(Detector::with_acceptable_pause(1000, TimeDelta::milliseconds(1000)));
+//! let monitor = Monitor { detector: Arc::clone(&detector) };
 //! ```
 //!
+use std::collections::VecDeque;
 use std::error::Error;
-use std::ops::Sub;
-use std::sync::{Arc};
-use tokio::sync::{RwLock, RwLockReadGuard};
+use std::f64::consts::{LN_10, LN_2, SQRT_2};
+use std::ops::{Add, Sub};
+use std::sync::{Arc, Mutex as SyncMutex, OnceLock, PoisonError, RwLock as SyncRwLock, TryLockError};
+use std::sync::atomic::{fence, AtomicI64, AtomicU64, Ordering};
+use tokio::sync::{Mutex, Notify, RwLock, RwLockReadGuard};
 use async_trait::async_trait;
-use libm::{erf, log10};
+use libm::{erfc, lgamma, log10};
+use rand::Rng;
 use chrono::{DateTime, Local, TimeDelta};
+use tokio::time::Instant;
+
+mod cluster;
+mod error;
+#[cfg(feature = "grafana")]
+mod grafana;
+mod heartbeat;
+mod instant;
+mod multi;
+mod node;
+mod registry;
+mod severity;
+mod sim;
+mod store;
+#[cfg(feature = "metrics")]
+mod telemetry;
+mod timestamp;
+
+pub use cluster::ClusterHealth;
+pub use error::{PhiError, StoreError, TryError};
+#[cfg(feature = "grafana")]
+pub use grafana::{SimpleJsonResponse, TimeSeries};
+pub use heartbeat::SelfHeartbeat;
+pub use instant::InstantDetector;
+use instant::ClockAnchor;
+pub use multi::MultiWindowDetector;
+pub use node::NodeId;
+pub use registry::{DEFAULT_SHUTDOWN_TIMEOUT, DetectorRegistry, HealthScore, HealthScoreOptions, NamespaceHandle, NamespacedRegistry, NodeFilter, SchedulerHandle, SchedulerOptions, TickStats, Transition, UnrankedPolicy};
+pub use severity::{Severity, SeverityBands};
+pub use sim::{simulate_stream, Outage, SimConfig, SimDistribution, SimResult};
+pub use store::{InMemoryStore, NodeSnapshot, NodeStore, StateStore, Versioned, WriteBackPolicy};
+pub use timestamp::Timestamp;
 
-/// Statistics of last window_length intervals
+/// Interval in ms as stored in the window, capped at `u32::MAX` with the `compact` feature.
+#[cfg(feature = "compact")]
+type Interval = u32;
+#[cfg(not(feature = "compact"))]
+type Interval = u64;
+
+/// Window storage, kept inline up to INLINE_INTERVALS intervals with the `smallvec` feature.
+#[cfg(feature = "smallvec")]
+type Intervals = smallvec::SmallVec<[Interval; INLINE_INTERVALS]>;
+#[cfg(not(feature = "smallvec"))]
+type Intervals = Vec<Interval>;
+
+#[cfg(feature = "smallvec")]
+const INLINE_INTERVALS: usize = 32;
+
+/// Statistics of last window_length intervals, over heartbeats timestamped with T
 #[derive(Clone, Debug)]
-pub struct Statistics {
-    arrival_intervals: Vec<u64>,
-    last_arrived_at: DateTime<Local>,
+pub struct Statistics<T = DateTime<Local>> {
+    arrival_intervals: Intervals,
+    last_arrived_at: T,
     window_length: u32,
     n: u32,
+    eviction_policy: EvictionPolicy,
+    last_accepted_at: T,
+    min_accept_interval: TimeDelta,
+    min_heartbeat_spacing_ms: Option<u64>,
+    rate_limited: u64,
+    outage_handling: OutageHandling,
+    outages: u64,
+    decay_kernel: DecayKernel,
+    /// Number of intervals recorded since creation, including the evicted ones.
+    intervals_seen: u64,
+    /// Sketch of every interval recorded since creation.
+    #[cfg(feature = "tdigest")]
+    digest: tdigest::TDigest,
+    incarnation: Option<u64>,
+    restarts: u64,
+    unhealthy: u64,
+    /// Whether each of the last STATUS_HISTORY_LENGTH status-carrying heartbeats was healthy.
+    recent_statuses: VecDeque<bool>,
+    seeded_moments: Option<(f64, f64)>,
+    started_at: T,
+    flap_history_capacity: Option<usize>,
+    arrival_phis: VecDeque<(T, f64)>,
+    /// Arrival of the heartbeat that ended the last breach of threshold, with the number of
+    /// heartbeats recorded since.
+    recovered_at: Option<(T, u32)>,
+    /// Set by `Detector::mark_down`, pinning phi at max_phi until the next accepted heartbeat.
+    marked_down: bool,
+    arrival_log_capacity: Option<usize>,
+    arrival_log: VecDeque<ArrivalLogEntry<T>>,
+    /// Moments for the reference they were computed for, reset whenever the window changes.
+    moments_cache: OnceLock<(VarianceReference, (f64, f64))>,
+    #[cfg(test)]
+    moments_computed: Arc<std::sync::atomic::AtomicUsize>,
 }
 
 /// Detector meant for abstraction over Statistics
 #[derive(Debug)]
 pub struct Detector {
     statistics: RwLock<Statistics>,
-    acceptable_pause: TimeDelta,
+    config: SyncRwLock<DetectorConfig>,
+    smoothed_phi: Mutex<Option<f64>>,
+    /// Woken whenever the statistics or the configuration change.
+    changed: Notify,
+    /// Bumped under the statistics write lock on every change, so that a cached phi is only
+    /// reused for the state it was computed from.
+    generation: AtomicU64,
+    last_phi: LastPhi,
+    /// Nanoseconds the last phi or moments computation took, u64::MAX before the first one.
+    #[cfg(feature = "timing")]
+    compute_nanos: AtomicU64,
+    shutdown_hooks: ShutdownHooks,
 }
 
-impl Detector {
-    /// New Detector instance with window_length. Recommended window_length is < 10000
-    pub fn new(window_length: u32) -> Self {
-        Detector {
-            statistics: RwLock::new(Statistics::new(window_length)),
-            acceptable_pause: TimeDelta::milliseconds(0),
-        }
-    }
+/// Callback run with the final summary of a Detector that shuts down
+type ShutdownHook = Box<dyn FnOnce(&DetectorSummary) + Send + Sync>;
 
-    /// New Detector instance with acceptable heartbeat pause duration.
-    pub fn with_acceptable_pause(window_length: u32, acceptable_pause: TimeDelta) -> Self {
-        Detector {
-            statistics: RwLock::new(Statistics::new(window_length)),
-            acceptable_pause,
-        }
+/// Callbacks registered through `Detector::on_shutdown`, each run once.
+#[derive(Default)]
+struct ShutdownHooks(SyncMutex<Vec<ShutdownHook>>);
+
+impl std::fmt::Debug for ShutdownHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hooks = self.0.lock().unwrap_or_else(PoisonError::into_inner).len();
+        f.debug_tuple("ShutdownHooks").field(&hooks).finish()
     }
 }
 
-impl Statistics {
-    /// New Statistics instance with window_length.
-    pub fn new(window_length: u32) -> Self {
-        Self {
-            arrival_intervals: vec![],
-            last_arrived_at: Local::now(),
-            window_length,
-            n: 0,
-        }
-    }
+/// Final state of a Detector, from `Detector::shutdown`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DetectorSummary {
+    /// Heartbeats recorded since creation or the last reset.
+    pub heartbeats: u64,
+    /// Intervals in the window.
+    pub sample_count: usize,
+    /// Mean and standard deviation of the window in ms, None if empty or if the configuration
+    /// cannot be read under `PoisonPolicy::Fail`.
+    pub mean_ms: Option<f64>,
+    pub std_dev_ms: Option<f64>,
+    pub last_arrived_at: Option<DateTime<Local>>,
+    /// Phi most recently computed, see `Detector::last_computed_phi`.
+    pub last_phi: Option<f64>,
+}
 
-    /// Insert heartbeat arrival time in window.
-    pub fn insert(&mut self, arrived_at: DateTime<Local>) {
+/// Most recently computed phi with the time and Detector generation it was computed for,
+/// readable without locking. A sequence number, odd while an update is in progress, lets
+/// readers detect torn reads.
+#[derive(Debug, Default)]
+struct LastPhi {
+    seq: AtomicU64,
+    phi: AtomicU64,
+    at_micros: AtomicI64,
+    generation: AtomicU64,
+}
 
-        // insert first element
-        if self.n == 0 {
-            self.last_arrived_at = arrived_at;
-            self.n += 1;
+impl LastPhi {
+    /// Record phi at t, skipped if another update is in progress since that one is as recent.
+    fn store(&self, phi: f64, t: DateTime<Local>, generation: u64) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        if seq % 2 == 1 || self.seq.compare_exchange(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed).is_err() {
             return;
         }
+        self.phi.store(phi.to_bits(), Ordering::Relaxed);
+        self.at_micros.store(t.timestamp_micros(), Ordering::Relaxed);
+        self.generation.store(generation, Ordering::Relaxed);
+        self.seq.store(seq + 2, Ordering::Release);
+    }
 
+    fn load(&self) -> Option<(f64, DateTime<Local>)> {
+        let (phi, at_micros, _) = self.read()?;
+        let at = DateTime::from_timestamp_micros(at_micros)?.with_timezone(&Local);
+        Some((phi, at))
+    }
 
-        if self.n - 1 == self.window_length {
-            self.arrival_intervals.remove(0);
-            self.n -= 1;
-        }
-        if self.n != 0 {
-            let arrival_interval = arrived_at.sub(self.last_arrived_at).num_milliseconds() as u64;
-            self.arrival_intervals.push(arrival_interval);
+    /// The recorded phi if it was computed for t in generation.
+    fn load_for(&self, t: DateTime<Local>, generation: u64) -> Option<f64> {
+        self.read()
+            .filter(|(_, at_micros, g)| *at_micros == t.timestamp_micros() && *g == generation)
+            .map(|(phi, _, _)| phi)
+    }
+
+    /// Consistent (phi, at_micros, generation), None before the first store.
+    fn read(&self) -> Option<(f64, i64, u64)> {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq == 0 {
+                return None;
+            }
+            if seq % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let phi = f64::from_bits(self.phi.load(Ordering::Relaxed));
+            let at_micros = self.at_micros.load(Ordering::Relaxed);
+            let generation = self.generation.load(Ordering::Relaxed);
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return Some((phi, at_micros, generation));
+            }
         }
-        self.last_arrived_at = arrived_at;
-        self.n += 1;
     }
 }
 
-/// PhiCore trait for mean and variance calculation
-#[async_trait]
-trait PhiCore {
-    /// Calculate mean with existing stats.
-    async fn mean_with_stats<'a>(&self, stats: Arc<RwLockReadGuard<'a, Statistics>>) -> Result<f64, Box<dyn Error>>;
+/// Default smoothing factor for the exponential moving average of phi.
+pub const DEFAULT_PHI_SMOOTHING: f64 = 0.5;
 
-    /// Calculate variance and mean with existing stats.
-    async fn variance_and_mean(&self) -> Result<(f64, f64), Box<dyn Error>>;
+/// Default phi above which a node is considered suspected.
+pub const DEFAULT_THRESHOLD: f64 = 8.0;
+
+/// Number of most recent status-carrying heartbeats `Detector::adjusted_phi` looks at.
+pub const STATUS_HISTORY_LENGTH: usize = 32;
+
+/// Maximum number of centroids of the t-digest behind `Detector::quantile`.
+#[cfg(feature = "tdigest")]
+pub const TDIGEST_SIZE: usize = 100;
+
+/// Default upper bound on phi, standing in for the infinite phi of a zero tail probability.
+pub const DEFAULT_MAX_PHI: f64 = 100.0;
+
+/// Default standard deviation, in ms, at or below which the distribution is treated as a point
+/// mass. Well below any real spread, yet far above the residue rounding leaves in the variance.
+pub const DEFAULT_STD_DEV_EPSILON: f64 = 1e-9;
+
+/// Default longest interval in ms `Detector::insert_checked` accepts, the longest the `compact`
+/// feature stores exactly (about 49 days).
+pub const DEFAULT_MAX_INTERVAL_MS: i64 = u32::MAX as i64;
+
+/// Model of the interval distribution phi is computed from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Distribution {
+    /// Normal distribution with the window's mean and variance.
+    #[default]
+    Normal,
+    /// Gaussian kernel density estimate over the window intervals. Each evaluation sums one
+    /// normal tail per interval, so phi costs O(window_length). `variance_reference` is ignored.
+    Kde { bandwidth: Bandwidth },
+    /// Gamma distribution with shape and rate fitted from the window mean and variance by the
+    /// method of moments, suited to positive right-skewed intervals.
+    Gamma,
+    /// Student's t-distribution with one degree of freedom less than the window has intervals,
+    /// scaled by the window's standard deviation. Its heavier tails account for the uncertain
+    /// variance of small windows, and it converges to the normal as the window fills. Falls back
+    /// to the normal below two intervals.
+    StudentT,
 }
 
-/// PhiInteraction trait for Detector
-#[async_trait]
-pub trait PhiInteraction {
-    /// Insertion of heartbeat arrival time.
-    async fn insert(&self, arrived_at: DateTime<Local>) -> Result<(), Box<dyn Error>>;
+/// Kernel bandwidth of `Distribution::Kde`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Bandwidth {
+    /// Silverman's rule of thumb, 1.06 * sigma * n^(-1/5), computed from the window.
+    #[default]
+    Silverman,
+    /// Fixed bandwidth in milliseconds.
+    Fixed(f64),
+}
 
-    /// Trait for phi for implementing struct
-    async fn phi(&self, t: DateTime<Local>) -> Result<f64, Box<dyn Error>>;
+/// Fitted distribution phi is computed from, in ms
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DistributionParams {
+    pub family: DistributionFamily,
+    /// Center of the distribution, the target interval with `VarianceReference::Target`.
+    pub mean: f64,
+    /// Standard deviation around mean, floored at min_std_deviation.
+    pub std_dev: f64,
+}
 
-    /// Last arrival time of heartbeat
-    async fn last_arrived_at(&self) -> Result<DateTime<Local>, Box<dyn Error>>;
+/// Distribution family of `DistributionParams` with its family-specific parameters
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DistributionFamily {
+    Normal,
+    /// Gaussian kernels of this bandwidth centered on every window interval.
+    Kde { bandwidth: f64 },
+    Gamma { shape: f64, rate: f64 },
+    StudentT { degrees_of_freedom: f64 },
 }
 
-/// Implementation of PhiCore for Detector
-#[async_trait]
-impl PhiCore for Detector {
-    async fn mean_with_stats<'a>(&self, stats: Arc<RwLockReadGuard<'a, Statistics>>) -> Result<f64, Box<dyn Error>> {
-        let mut mean: f64 = 0.;
-        let len = &stats.arrival_intervals.len();
-        for v in &stats.arrival_intervals {
-            mean += *v as f64 / *len as f64;
+/// Aggregates of the window intervals, in ms
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntervalsSummary {
+    /// Number of intervals.
+    pub count: usize,
+    /// Sum of the intervals, saturating at `u64::MAX`.
+    pub sum_ms: u64,
+    /// Shortest interval.
+    pub min_ms: u64,
+    /// Longest interval.
+    pub max_ms: u64,
+}
+
+/// Changes of the window between two Statistics, from `Statistics::diff`
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatisticsDiff {
+    /// Change of the mean in ms.
+    pub mean_delta: f64,
+    /// Change of the variance around the mean in ms².
+    pub variance_delta: f64,
+    /// Change of the number of intervals.
+    pub sample_count_delta: i64,
+    /// Intervals in ms only in the later window, ascending.
+    pub entered: Vec<u64>,
+    /// Intervals in ms only in the earlier window, ascending.
+    pub left: Vec<u64>,
+}
+
+/// How stale a node is at a given time, gathered from a single snapshot of its detector
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StalenessReport {
+    /// Time since the last heartbeat, None if no heartbeat arrived yet.
+    pub elapsed: Option<TimeDelta>,
+    pub phi: f64,
+    /// Number of intervals in the window.
+    pub samples: usize,
+    /// Whether at least min_samples intervals are known.
+    pub warmed_up: bool,
+    pub last_arrived_at: Option<DateTime<Local>>,
+}
+
+/// Heartbeat recorded in the arrival log
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArrivalLogEntry<T = DateTime<Local>> {
+    pub arrived_at: T,
+    /// Why the heartbeat was not recorded as an interval, None if it was accepted.
+    pub rejection: Option<Rejection>,
+}
+
+/// Reason a heartbeat was not recorded as an interval
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    /// Arrived within `min_heartbeat_spacing_ms` of the last heartbeat and was ignored.
+    Debounced,
+    /// Arrived within `min_accept_interval` of the last accepted heartbeat.
+    RateLimited,
+    /// Arrived before the last heartbeat.
+    OutOfOrder,
+}
+
+/// Availability decision for a node with how far it can be trusted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Evaluation {
+    pub phi: f64,
+    /// Whether phi is at or below the threshold.
+    pub available: bool,
+    /// Number of intervals in the window.
+    pub sample_count: usize,
+    /// Fraction of the window filled with intervals, in [0, 1].
+    pub confidence: f64,
+}
+
+/// Entry of a recorded session fed to `Detector::replay`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// Heartbeat arriving at the time.
+    Heartbeat(DateTime<Local>),
+    /// Phi evaluated at the time.
+    Query(DateTime<Local>),
+}
+
+/// Status of a node at a given time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeStatus {
+    /// No heartbeat has arrived yet.
+    Idle,
+    /// Fewer than min_samples intervals are known, so phi is not trusted yet.
+    Warming(f64),
+    /// Phi is at or below the threshold.
+    Healthy(f64),
+    /// Phi is above the threshold.
+    Suspected(f64),
+    /// Phi is at or below the threshold, but the node only recently recovered from a breach
+    /// and is serving the configured `ProbationPolicy`.
+    Probation(f64),
+}
+
+/// How long a node stays in `NodeStatus::Probation` once a heartbeat ends a breach of threshold.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProbationPolicy {
+    /// Recovered nodes are healthy right away.
+    #[default]
+    Disabled,
+    /// For the duration after the recovering heartbeat.
+    For(TimeDelta),
+    /// Until that many further heartbeats arrived after the recovering one.
+    Heartbeats(u32),
+}
+
+/// How `insert_gap` records the span covering known-missed heartbeats.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GapHandling {
+    /// Split the elapsed time into `missed + 1` equal synthetic intervals.
+    #[default]
+    Split,
+    /// Drop the gap from the window and only move `last_arrived_at` forward.
+    Discard,
+}
+
+/// How `insert` records intervals far longer than usual, such as the one ending an outage.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutageHandling {
+    /// Record them in the window like any other interval.
+    #[default]
+    Record,
+    /// Count intervals longer than the factor times the window mean in `Detector::outage_count`
+    /// instead of recording them, so that one outage does not skew the distribution.
+    Exclude(f64),
+}
+
+/// Which interval `insert` evicts once the window is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Evict the oldest interval.
+    #[default]
+    Oldest,
+    /// Evict the interval farthest from the window mean, the oldest one on ties.
+    LargestOutlier,
+    /// Keep a uniform random sample of every interval ever seen (Algorithm R), so the window
+    /// represents the entire history instead of the most recent intervals. The window is no
+    /// longer in arrival order once full.
+    Reservoir,
+}
+
+/// Weighting of the window intervals by age in the mean and variance.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DecayKernel {
+    /// Every interval weighs the same.
+    #[default]
+    Uniform,
+    /// Weights fall linearly from 1 for the newest interval to 1/n for the oldest of n.
+    Linear,
+    /// The interval k places before the newest weighs e^(-lambda * k).
+    Exponential(f64),
+}
+
+impl DecayKernel {
+    /// Weight of the interval age places before the newest of len intervals.
+    fn weight(self, age: usize, len: usize) -> f64 {
+        match self {
+            DecayKernel::Uniform => 1.,
+            DecayKernel::Linear => (len - age) as f64 / len as f64,
+            DecayKernel::Exponential(lambda) => (-lambda * age as f64).exp(),
         }
-        Ok(mean)
     }
+}
 
-    async fn variance_and_mean(&self) -> Result<(f64, f64), Box<dyn Error>> {
-        let mut variance: f64 = 0.;
-        let stats = Arc::new(self.statistics.read().await);
-        let mu = self.mean_with_stats(Arc::clone(&stats)).await?;
-        let len = &stats.arrival_intervals.len();
-        for v in &stats.arrival_intervals {
-            let val = ((*v as f64 - mu) * (*v as f64 - mu)) / *len as f64;
-            variance += val;
+/// Center around which the variance is measured and phi is evaluated.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VarianceReference {
+    /// The mean of the sampled intervals.
+    #[default]
+    SampleMean,
+    /// A known target interval in milliseconds, so a consistently slow node registers as deviant.
+    Target(f64),
+}
+
+/// Blend of the variance of the trailing short_window intervals, which reacts quickly, with the
+/// variance of the whole window, which is stable: `weight * short + (1 - weight) * long`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VarianceBlend {
+    pub short_window: u32,
+    /// Weight of the short-window variance, in [0, 1].
+    pub weight: f64,
+}
+
+/// Handling of the configuration lock once a panic while holding it poisoned it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// Keep using the configuration inside the poisoned lock.
+    #[default]
+    Recover,
+    /// Fail every operation reading the configuration with `PhiError::Poisoned`.
+    Fail,
+}
+
+/// Tunables of a single Detector.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DetectorConfig {
+    /// Number of arrival intervals kept in the window.
+    pub window_length: u32,
+    /// Heartbeat pause duration during which the detector will not raise suspicion.
+    pub acceptable_pause: TimeDelta,
+    /// Phi above which the node is considered suspected.
+    pub threshold: f64,
+    /// Smoothing factor in (0, 1] for `smoothed_phi`.
+    pub phi_smoothing: f64,
+    /// Handling of known heartbeat gaps in `insert_gap`.
+    pub gap_handling: GapHandling,
+    /// Intervals needed before the node is considered warmed up.
+    pub min_samples: u32,
+    /// Interval evicted once the window is full.
+    pub eviction_policy: EvictionPolicy,
+    /// Lower bound on the standard deviation used by phi, so that very regular heartbeats do not
+    /// make the detector overly sensitive.
+    pub min_std_deviation: TimeDelta,
+    /// Heartbeats closer than this to the previously accepted one only refresh `last_arrived_at`
+    /// and are not recorded as intervals. Zero accepts every heartbeat.
+    pub min_accept_interval: TimeDelta,
+    /// Center of the distribution phi is computed against.
+    pub variance_reference: VarianceReference,
+    /// Upper bound on phi. Phi is always within `[0, max_phi]`.
+    pub max_phi: f64,
+    /// Lower bound on phi outside the startup grace, so that no node is ever perfectly trusted.
+    pub min_phi: f64,
+    /// Period after creation or `rebase_clock` during which phi is 0 and the node assumed available.
+    pub startup_grace: TimeDelta,
+    /// Model of the interval distribution.
+    pub distribution: Distribution,
+    /// Heartbeats arriving less than this many ms after the last one are ignored entirely,
+    /// coalescing micro-bursts into their first heartbeat.
+    pub min_heartbeat_spacing_ms: Option<u64>,
+    /// Number of most recent heartbeats kept in the arrival log, None disables the log.
+    pub arrival_log: Option<usize>,
+    /// Bands `severity` maps phi into.
+    pub severity_bands: SeverityBands,
+    /// Whether a configuration lock poisoned by a panicking writer is recovered or reported.
+    pub poison_policy: PoisonPolicy,
+    /// Weighting of the window intervals by age in the mean and variance.
+    pub decay_kernel: DecayKernel,
+    /// Standard deviation in ms at or below which the distribution is treated as a point mass
+    /// rather than producing extreme z-scores, applied after min_std_deviation.
+    pub std_dev_epsilon: f64,
+    /// Longest interval `Detector::insert_checked` accepts, as a guard against corrupted timestamps.
+    pub max_interval: TimeDelta,
+    /// Probation `Detector::status` reports for nodes recovering from a breach of threshold.
+    pub probation: ProbationPolicy,
+    /// Whether intervals far longer than the window mean are recorded or only counted.
+    pub outage_handling: OutageHandling,
+    /// Blend of a short-window and the whole-window variance used for phi, the latter alone if None.
+    pub variance_blend: Option<VarianceBlend>,
+    /// Number of most recent heartbeats whose phi at arrival is kept for `Detector::flap_count_at`,
+    /// None disables the history and with it flap counting.
+    pub flap_history: Option<usize>,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        DetectorConfig {
+            window_length: 1000,
+            acceptable_pause: TimeDelta::milliseconds(0),
+            threshold: DEFAULT_THRESHOLD,
+            phi_smoothing: DEFAULT_PHI_SMOOTHING,
+            gap_handling: GapHandling::default(),
+            min_samples: 1,
+            eviction_policy: EvictionPolicy::default(),
+            min_std_deviation: TimeDelta::milliseconds(0),
+            min_accept_interval: TimeDelta::milliseconds(0),
+            variance_reference: VarianceReference::default(),
+            max_phi: DEFAULT_MAX_PHI,
+            min_phi: 0.,
+            startup_grace: TimeDelta::milliseconds(0),
+            distribution: Distribution::default(),
+            min_heartbeat_spacing_ms: None,
+            arrival_log: None,
+            severity_bands: SeverityBands::default(),
+            poison_policy: PoisonPolicy::default(),
+            decay_kernel: DecayKernel::default(),
+            std_dev_epsilon: DEFAULT_STD_DEV_EPSILON,
+            max_interval: TimeDelta::milliseconds(DEFAULT_MAX_INTERVAL_MS),
+            probation: ProbationPolicy::default(),
+            outage_handling: OutageHandling::default(),
+            variance_blend: None,
+            flap_history: None,
         }
-        Ok((variance, mu))
     }
 }
 
-/// Cumulative distribution function for normal distribution
-fn normal_cdf(t: f64, mu: f64, sigma: f64) -> f64 {
-    if sigma == 0. {
-        return if t == mu {
-            1.
-        } else {
-            0.
-        };
+impl DetectorConfig {
+    /// This config read out of a poisoned lock, as its poison_policy demands.
+    fn recover_poisoned(self) -> Result<DetectorConfig, PhiError> {
+        match self.poison_policy {
+            PoisonPolicy::Recover => Ok(self),
+            PoisonPolicy::Fail => Err(PhiError::Poisoned),
+        }
     }
 
-    let z = (t - mu) / sigma;
-    0.5 + 0.5 * (erf(z))
+    /// Check that every tunable is within its valid range.
+    pub fn validate(&self) -> Result<(), PhiError> {
+        let invalid = |reason: &str| Err(PhiError::InvalidConfig(reason.to_string()));
+        if self.window_length == 0 {
+            return invalid("window_length must be positive");
+        }
+        if self.min_samples > self.window_length {
+            return Err(PhiError::InvalidConfig(format!(
+                "min_samples ({}) must not exceed window_length ({}), or the node never warms up",
+                self.min_samples, self.window_length,
+            )));
+        }
+        if self.acceptable_pause < TimeDelta::zero() {
+            return invalid("acceptable_pause must not be negative");
+        }
+        if self.threshold.is_nan() || self.threshold < 0. {
+            return invalid("threshold must not be negative or NaN");
+        }
+        if self.phi_smoothing.is_nan() || self.phi_smoothing <= 0. || self.phi_smoothing > 1. {
+            return invalid("phi_smoothing must be in (0, 1]");
+        }
+        if self.min_std_deviation < TimeDelta::zero() {
+            return invalid("min_std_deviation must not be negative");
+        }
+        if self.min_accept_interval < TimeDelta::zero() {
+            return invalid("min_accept_interval must not be negative");
+        }
+        if let VarianceReference::Target(target) = self.variance_reference {
+            if !target.is_finite() || target < 0. {
+                return invalid("variance_reference target must be finite and not negative");
+            }
+        }
+        if !self.max_phi.is_finite() || self.max_phi < 0. {
+            return invalid("max_phi must be finite and not negative");
+        }
+        if !self.min_phi.is_finite() || self.min_phi < 0. || self.min_phi > self.max_phi {
+            return invalid("min_phi must be finite, not negative and not above max_phi");
+        }
+        if self.startup_grace < TimeDelta::zero() {
+            return invalid("startup_grace must not be negative");
+        }
+        if let Distribution::Kde { bandwidth: Bandwidth::Fixed(bandwidth) } = self.distribution {
+            if !bandwidth.is_finite() || bandwidth <= 0. {
+                return invalid("kde bandwidth must be finite and positive");
+            }
+        }
+        if let DecayKernel::Exponential(lambda) = self.decay_kernel {
+            if !lambda.is_finite() || lambda < 0. {
+                return invalid("exponential decay lambda must be finite and not negative");
+            }
+        }
+        if !self.std_dev_epsilon.is_finite() || self.std_dev_epsilon < 0. {
+            return invalid("std_dev_epsilon must be finite and not negative");
+        }
+        if self.max_interval <= TimeDelta::zero() {
+            return invalid("max_interval must be positive");
+        }
+        if let ProbationPolicy::For(duration) = self.probation {
+            if duration < TimeDelta::zero() {
+                return invalid("probation duration must not be negative");
+            }
+        }
+        if let OutageHandling::Exclude(factor) = self.outage_handling {
+            if !factor.is_finite() || factor < 1. {
+                return invalid("outage exclusion factor must be finite and at least 1");
+            }
+        }
+        if let Some(blend) = self.variance_blend {
+            if blend.short_window == 0 || !(0. ..=1.).contains(&blend.weight) {
+                return invalid("variance blend needs a positive short_window and a weight in [0, 1]");
+            }
+        }
+        if self.arrival_log == Some(0) {
+            return invalid("arrival_log capacity must be positive");
+        }
+        if self.flap_history == Some(0) {
+            return invalid("flap_history capacity must be positive");
+        }
+        Ok(())
+    }
 }
 
-/// Implementation of PhiInteraction for Detector
-#[async_trait]
-impl PhiInteraction for Detector {
-    async fn insert(&self, arrived_at: DateTime<Local>) -> Result<(), Box<dyn Error>> {
-        let mut stats = self.statistics.write().await;
-        stats.insert(arrived_at);
-        Ok(())
+/// Builder for a Detector, starting from the default DetectorConfig.
+#[derive(Clone, Debug, Default)]
+pub struct DetectorBuilder {
+    config: DetectorConfig,
+}
+
+impl DetectorBuilder {
+    /// New builder with the default DetectorConfig.
+    pub fn new() -> Self {
+        DetectorBuilder::default()
     }
 
-    async fn phi(&self, t: DateTime<Local>) -> Result<f64, Box<dyn Error>> {
-        let (sigma_sq, mu) = self.variance_and_mean().await?;
-        let sigma = sigma_sq.sqrt();
-        let last_arrived_at = self.last_arrived_at().await?;
-        let time_diff = t.sub(last_arrived_at).sub(self.acceptable_pause);
-        let ft = normal_cdf(time_diff.num_milliseconds() as f64, mu, sigma);
-        let phi = -log10(1. - ft);
-        Ok(phi)
+    /// Number of arrival intervals kept in the window.
+    pub fn window_length(mut self, window_length: u32) -> Self {
+        self.config.window_length = window_length;
+        self
     }
 
-    async fn last_arrived_at(&self) -> Result<DateTime<Local>, Box<dyn Error>> {
-        Ok(self.statistics.read().await.last_arrived_at)
+    /// Heartbeat pause duration during which the detector will not raise suspicion.
+    pub fn acceptable_pause(mut self, acceptable_pause: TimeDelta) -> Self {
+        self.config.acceptable_pause = acceptable_pause;
+        self
+    }
+
+    /// Phi above which the node is considered suspected.
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.config.threshold = threshold;
+        self
+    }
+
+    /// Smoothing factor in (0, 1] for `smoothed_phi`.
+    pub fn phi_smoothing(mut self, phi_smoothing: f64) -> Self {
+        self.config.phi_smoothing = phi_smoothing;
+        self
+    }
+
+    /// Handling of known heartbeat gaps in `insert_gap`.
+    pub fn gap_handling(mut self, gap_handling: GapHandling) -> Self {
+        self.config.gap_handling = gap_handling;
+        self
+    }
+
+    /// Intervals needed before the node is considered warmed up.
+    pub fn min_samples(mut self, min_samples: u32) -> Self {
+        self.config.min_samples = min_samples;
+        self
+    }
+
+    /// Interval evicted once the window is full.
+    pub fn eviction_policy(mut self, eviction_policy: EvictionPolicy) -> Self {
+        self.config.eviction_policy = eviction_policy;
+        self
+    }
+
+    /// Lower bound on the standard deviation used by phi.
+    pub fn min_std_deviation(mut self, min_std_deviation: TimeDelta) -> Self {
+        self.config.min_std_deviation = min_std_deviation;
+        self
+    }
+
+    /// Center around which the variance is measured and phi is evaluated.
+    pub fn variance_reference(mut self, variance_reference: VarianceReference) -> Self {
+        self.config.variance_reference = variance_reference;
+        self
+    }
+
+    /// Known fixed heartbeat period of the sender. Phi is then centered on the period and only
+    /// the jitter around it is learned, shorthand for `VarianceReference::Target`.
+    pub fn known_period(self, period: TimeDelta) -> Self {
+        self.variance_reference(VarianceReference::Target(period.num_milliseconds() as f64))
+    }
+
+    /// Minimum spacing between heartbeats recorded as intervals.
+    pub fn min_accept_interval(mut self, min_accept_interval: TimeDelta) -> Self {
+        self.config.min_accept_interval = min_accept_interval;
+        self
+    }
+
+    /// Upper bound on phi.
+    pub fn max_phi(mut self, max_phi: f64) -> Self {
+        self.config.max_phi = max_phi;
+        self
+    }
+
+    /// Lower bound on phi.
+    pub fn min_phi(mut self, min_phi: f64) -> Self {
+        self.config.min_phi = min_phi;
+        self
+    }
+
+    /// Period after start during which phi is suppressed.
+    pub fn startup_grace(mut self, startup_grace: TimeDelta) -> Self {
+        self.config.startup_grace = startup_grace;
+        self
+    }
+
+    /// Model of the interval distribution.
+    pub fn distribution(mut self, distribution: Distribution) -> Self {
+        self.config.distribution = distribution;
+        self
+    }
+
+    /// Minimum spacing in ms below which heartbeats are ignored.
+    pub fn min_heartbeat_spacing_ms(mut self, min_heartbeat_spacing_ms: u64) -> Self {
+        self.config.min_heartbeat_spacing_ms = Some(min_heartbeat_spacing_ms);
+        self
+    }
+
+    /// Keep the last capacity heartbeats, accepted or rejected, in an arrival log.
+    pub fn with_arrival_log(mut self, capacity: usize) -> Self {
+        self.config.arrival_log = Some(capacity);
+        self
+    }
+
+    /// Keep the phi at arrival of the last capacity heartbeats for `Detector::flap_count_at`.
+    pub fn with_flap_history(mut self, capacity: usize) -> Self {
+        self.config.flap_history = Some(capacity);
+        self
+    }
+
+    /// Bands phi is mapped into by `severity`.
+    pub fn severity_bands(mut self, severity_bands: SeverityBands) -> Self {
+        self.config.severity_bands = severity_bands;
+        self
+    }
+
+    /// Handling of a poisoned configuration lock.
+    pub fn poison_policy(mut self, poison_policy: PoisonPolicy) -> Self {
+        self.config.poison_policy = poison_policy;
+        self
+    }
+
+    /// Weighting of the window intervals by age.
+    pub fn decay_kernel(mut self, decay_kernel: DecayKernel) -> Self {
+        self.config.decay_kernel = decay_kernel;
+        self
+    }
+
+    /// Standard deviation in ms at or below which the distribution is treated as a point mass.
+    pub fn std_dev_epsilon(mut self, std_dev_epsilon: f64) -> Self {
+        self.config.std_dev_epsilon = std_dev_epsilon;
+        self
+    }
+
+    /// Longest interval `Detector::insert_checked` accepts.
+    pub fn max_interval(mut self, max_interval: TimeDelta) -> Self {
+        self.config.max_interval = max_interval;
+        self
+    }
+
+    /// Probation of nodes recovering from a breach of threshold.
+    pub fn probation(mut self, probation: ProbationPolicy) -> Self {
+        self.config.probation = probation;
+        self
+    }
+
+    /// Whether intervals far longer than the window mean are recorded or only counted.
+    pub fn outage_handling(mut self, outage_handling: OutageHandling) -> Self {
+        self.config.outage_handling = outage_handling;
+        self
+    }
+
+    /// Blend the variance of the trailing short_window intervals into phi with weight.
+    pub fn variance_blend(mut self, short_window: u32, weight: f64) -> Self {
+        self.config.variance_blend = Some(VarianceBlend { short_window, weight });
+        self
+    }
+
+    /// Configuration built so far.
+    pub fn config(&self) -> &DetectorConfig {
+        &self.config
+    }
+
+    /// Build the Detector without validating the configuration, see `try_build`.
+    pub fn build(self) -> Detector {
+        Detector::from_config(self.config)
+    }
+
+    /// Build the Detector, failing with `PhiError::InvalidConfig` if the configuration is invalid,
+    /// e.g. when min_samples exceeds window_length.
+    pub fn try_build(self) -> Result<Detector, PhiError> {
+        self.config.validate()?;
+        Ok(self.build())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::ops::Add;
-    use chrono::{Duration, Local, TimeDelta};
-    use tokio::sync::RwLock;
-    use crate::{Detector, PhiCore, PhiInteraction, Statistics};
+impl Detector {
+    /// New Detector instance with window_length. Recommended window_length is < 10000
+    pub fn new(window_length: u32) -> Self {
+        Detector::from_config(DetectorConfig { window_length, ..Default::default() })
+    }
 
-    #[tokio::test]
-    async fn test_variant_mean_and_variance_combo_calculation() {
-        let mut stats = Statistics::new(10);
-        let mut i = 0;
-        let mut curr_time = Local::now();
-        &stats.insert(curr_time.clone());
-        let expect_vals = [1630, 4421, 1514, 216, 231, 931, 4182, 102, 104, 241, 5132];
-        while i < expect_vals.len() {
-            curr_time = curr_time.add(Duration::milliseconds(expect_vals[i]));
-            let arrived_at = curr_time;
-            &stats.insert(arrived_at);
-            i += 1;
+    /// Builder starting from the default DetectorConfig, same as `DetectorBuilder::new`.
+    ///
+    /// ```rust
+    /// use chrono::TimeDelta;
+    /// use phi_accrual_detector::{Detector, PhiInteraction};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let detector = Detector::builder().window_length(100).min_std_deviation(TimeDelta::milliseconds(10)).build();
+    /// for _ in 0..3 {
+    ///     detector.insert_after_ms(100).await.unwrap();
+    /// }
+    /// let last = detector.last_arrived_at().await.unwrap();
+    /// assert!(detector.phi(last + TimeDelta::milliseconds(50)).await.unwrap() < 1.);
+    /// # });
+    /// ```
+    pub fn builder() -> DetectorBuilder {
+        DetectorBuilder::new()
+    }
+
+    /// New Detector instance with acceptable heartbeat pause duration.
+    pub fn with_acceptable_pause(window_length: u32, acceptable_pause: TimeDelta) -> Self {
+        Detector::from_config(DetectorConfig { window_length, acceptable_pause, ..Default::default() })
+    }
+
+    /// New Detector instance with a smoothing factor in (0, 1] for `smoothed_phi`.
+    /// Higher values follow the raw phi more closely.
+    pub fn with_phi_smoothing(window_length: u32, phi_smoothing: f64) -> Self {
+        Detector::from_config(DetectorConfig { window_length, phi_smoothing, ..Default::default() })
+    }
+
+    /// New Detector instance with the given handling of known heartbeat gaps.
+    pub fn with_gap_handling(window_length: u32, gap_handling: GapHandling) -> Self {
+        Detector::from_config(DetectorConfig { window_length, gap_handling, ..Default::default() })
+    }
+
+    /// New Detector instance from a DetectorConfig.
+    pub fn from_config(mut config: DetectorConfig) -> Self {
+        config.phi_smoothing = config.phi_smoothing.clamp(f64::MIN_POSITIVE, 1.);
+        let mut statistics = Statistics::new(config.window_length);
+        statistics.configure(&config);
+        Detector {
+            statistics: RwLock::new(statistics),
+            config: SyncRwLock::new(config),
+            smoothed_phi: Mutex::new(None),
+            changed: Notify::new(),
+            generation: AtomicU64::new(0),
+            last_phi: LastPhi::default(),
+            #[cfg(feature = "timing")]
+            compute_nanos: AtomicU64::new(u64::MAX),
+            shutdown_hooks: ShutdownHooks::default(),
         }
-        let detector = Detector {
-            statistics: RwLock::new(stats),
-            acceptable_pause: TimeDelta::milliseconds(0),
-        };
-        let (mut variance, mut mean) = detector.variance_and_mean().await.unwrap();
-        mean = (mean * 100.0).round() * 0.01;
-        variance = (variance * 100.0).round() * 0.01;
-        assert_eq!(1707.4, mean);
-        assert_eq!(3755791.64, variance);
+    }
 
-        let mut suspicion_level: Vec<f64> = vec![];
-        for i in 1..10 {
-            curr_time = curr_time.add(Duration::milliseconds(250));
-            suspicion_level.push(detector.phi(curr_time).await.unwrap())
+    /// New Detector instance replaying a sorted log of historical arrivals.
+    pub fn from_history(config: DetectorConfig, arrivals: &[DateTime<Local>]) -> Result<Self, PhiError> {
+        let mut statistics = Statistics::rebuild(arrivals, config.window_length)?;
+        statistics.configure(&config);
+        if !statistics.has_plain_inserts() {
+            statistics = Statistics::new(config.window_length);
+            statistics.configure(&config);
+            for arrived_at in arrivals {
+                statistics.insert(*arrived_at);
+            }
         }
-        println!("suspicion -> {:?}", suspicion_level);
-        for i in 1..suspicion_level.len() {
-            assert!(suspicion_level[i] > suspicion_level[i - 1]);
+        Ok(Detector {
+            statistics: RwLock::new(statistics),
+            ..Detector::from_config(config)
+        })
+    }
+
+    /// New Detector instance around existing statistics, resized to the configured window.
+    pub fn from_statistics(config: DetectorConfig, statistics: Statistics) -> Self {
+        let mut statistics = statistics.with_window_length(config.window_length);
+        statistics.configure(&config);
+        Detector {
+            statistics: RwLock::new(statistics),
+            ..Detector::from_config(config)
         }
     }
 
-    #[tokio::test]
-    async fn test_constant_phi_with_constant_pings_calculation() {
-        let stats = Statistics::new(10);
-        let detector = Detector {
-            statistics: RwLock::new(stats),
-            acceptable_pause: TimeDelta::milliseconds(0),
-        };
-        let mut i = 0;
-        let mut curr_time = Local::now();
+    /// Copy of the config and statistics, e.g. for persisting them in a NodeStore.
+    pub async fn snapshot(&self) -> Result<NodeSnapshot, PhiError> {
+        Ok(NodeSnapshot {
+            config: self.read_config()?,
+            statistics: self.statistics.read().await.clone(),
+        })
+    }
+
+    /// Independent Detector starting from a copy of this one's config and statistics,
+    /// e.g. to compare two configurations from the same learned distribution.
+    pub async fn fork(&self) -> Result<Detector, PhiError> {
+        Ok(self.snapshot().await?.into_detector())
+    }
+
+    /// Status of the node at t relative to the configured threshold and min_samples. Reuses
+    /// the phi last computed for t if nothing changed since.
+    pub async fn status(&self, t: DateTime<Local>) -> Result<NodeStatus, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        if stats.n == 0 {
+            return Ok(NodeStatus::Idle);
+        }
+        let phi = self.cached_phi_of(&stats, t, &config);
+        Ok(if stats.arrival_intervals.len() < config.min_samples as usize {
+            NodeStatus::Warming(phi)
+        } else if phi > config.threshold {
+            NodeStatus::Suspected(phi)
+        } else if stats.in_probation(t, &config) {
+            NodeStatus::Probation(phi)
+        } else {
+            NodeStatus::Healthy(phi)
+        })
+    }
+
+    /// Whether phi at t has flatlined at max_phi, so that later t no longer raise it. Under the
+    /// normal distribution phi keeps growing until the tail probability underflows around a
+    /// z-score of 38, where phi is about 323, and is max_phi from there if not capped earlier.
+    pub async fn is_phi_saturated(&self, t: DateTime<Local>) -> Result<bool, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        Ok(stats.n != 0 && self.phi_of(&stats, t, &config) >= config.max_phi)
+    }
+
+    /// Whether phi at t is at or below the configured threshold, so that nodes with different
+    /// thresholds can be checked uniformly.
+    pub async fn is_available_default(&self, t: DateTime<Local>) -> Result<bool, Box<dyn Error>> {
+        let config = self.read_config()?;
+        Ok(self.phi_with_config(t, &config).await? <= config.threshold)
+    }
+
+    /// Probability in [0, 1] that the node is still alive at t, i.e. that an interval at least as
+    /// long as the observed delay occurs. Equal to 10^-phi, so it decreases as phi grows.
+    pub async fn liveness_probability(&self, t: DateTime<Local>) -> Result<f64, Box<dyn Error>> {
+        let phi = self.phi(t).await?;
+        Ok(10f64.powf(-phi))
+    }
+
+    /// Severity band of the phi at t.
+    pub async fn severity(&self, t: DateTime<Local>) -> Result<Severity, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let phi = self.phi_with_config(t, &config).await?;
+        Ok(config.severity_bands.classify(phi))
+    }
+
+    /// Time between the last heartbeat and now, None if no heartbeat arrived yet.
+    pub async fn time_since_last_heartbeat(&self, now: DateTime<Local>) -> Result<Option<TimeDelta>, Box<dyn Error>> {
+        let stats = self.statistics.read().await;
+        Ok((stats.n != 0).then(|| now.sub(stats.last_arrived_at)))
+    }
+
+    /// Time between the last heartbeat and now, failing with `PhiError::NoHeartbeat` before the first one.
+    pub async fn time_since_last(&self, now: DateTime<Local>) -> Result<TimeDelta, Box<dyn Error>> {
+        Ok(self.time_since_last_heartbeat(now).await?.ok_or(PhiError::NoHeartbeat)?)
+    }
+
+    /// Time between the last heartbeat and the current time, see `time_since_last`.
+    pub async fn time_since_last_now(&self) -> Result<TimeDelta, Box<dyn Error>> {
+        self.time_since_last(Local::now()).await
+    }
+
+    /// Whether more than the expected interval, the center phi is evaluated around, has passed
+    /// since the last heartbeat at now. A cheap filter ahead of computing phi, false until an
+    /// interval is known and true while marked down.
+    pub async fn is_overdue(&self, now: DateTime<Local>) -> Result<bool, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        if stats.marked_down {
+            return Ok(true);
+        }
+        if stats.n == 0 || (stats.arrival_intervals.is_empty() && stats.seeded_moments.is_none()) {
+            return Ok(false);
+        }
+        let expected = stats.moments(config.variance_reference).1;
+        Ok(now.sub(stats.last_arrived_at).num_milliseconds() as f64 > expected)
+    }
+
+    /// Whether phi has stayed above threshold for at least duration up to now, so that a brief
+    /// spike does not raise an alert. Phi only rises until the next heartbeat, so it has exceeded
+    /// threshold ever since it first crossed it after the last heartbeat.
+    pub async fn sustained_over_threshold(&self, threshold: f64, duration: TimeDelta, now: DateTime<Local>) -> Result<bool, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let crossed_at = self.statistics.read().await.suspected_at(threshold, &config);
+        Ok(crossed_at.is_some_and(|at| now.sub(at) >= duration))
+    }
+
+    /// Phi at now with the availability decision against threshold and the confidence in it,
+    /// all read under a single lock. A node without heartbeats has phi 0.
+    pub async fn evaluate(&self, now: DateTime<Local>, threshold: f64) -> Result<Evaluation, Box<dyn Error>> {
+        let config = self.read_config()?;
+        Ok(self.statistics.read().await.evaluate(now, threshold, &config))
+    }
+
+    /// Number of further intervals needed before the confidence of `evaluate` reaches
+    /// target_confidence, clamped to [0, 1]. 0 once it is reached.
+    pub async fn heartbeats_until_confident(&self, target_confidence: f64) -> u32 {
+        let stats = self.statistics.read().await;
+        let needed = (target_confidence.clamp(0., 1.) * stats.window_length as f64).ceil() as usize;
+        needed.saturating_sub(stats.arrival_intervals.len()) as u32
+    }
+
+    /// Record a heartbeat and return the evaluation at arrived_at as it stood right before it,
+    /// e.g. to log how close the node came to being suspected. Both happen under one write
+    /// lock: phi is computed against the pre-insert statistics first, then the heartbeat is inserted.
+    pub async fn insert_and_evaluate(&self, arrived_at: DateTime<Local>, threshold: f64) -> Result<Evaluation, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        let evaluation = stats.evaluate(arrived_at, threshold, &config);
+        stats.record_arrival_phi(arrived_at, &config);
+        stats.insert(arrived_at);
+        self.notify_changed();
+        Ok(evaluation)
+    }
+
+    /// Elapsed time, phi and sample count at now, all read under a single lock.
+    pub async fn staleness(&self, now: DateTime<Local>) -> Result<StalenessReport, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        let samples = stats.arrival_intervals.len();
+        let beaten = stats.n != 0;
+        Ok(StalenessReport {
+            elapsed: beaten.then(|| now.sub(stats.last_arrived_at)),
+            phi: if beaten { stats.phi_at(now, &config) } else { 0. },
+            samples,
+            warmed_up: beaten && samples >= config.min_samples as usize,
+            last_arrived_at: beaten.then_some(stats.last_arrived_at),
+        })
+    }
+
+    /// Check the internal invariants of the detector, describing the first violated one.
+    /// Only available in debug builds, as a safety net for tests.
+    #[cfg(debug_assertions)]
+    pub async fn verify_invariants(&self) -> Result<(), String> {
+        const CLOCK_TOLERANCE: TimeDelta = TimeDelta::minutes(1);
+        let config = self.read_config().map_err(|e| e.to_string())?;
+        config.validate().map_err(|e| e.to_string())?;
+        let stats = self.statistics.read().await;
+        let len = stats.arrival_intervals.len();
+        if len > stats.window_length as usize {
+            return Err(format!("{} intervals exceed window_length {}", len, stats.window_length));
+        }
+        if stats.window_length != config.window_length {
+            return Err(format!("window_length {} differs from config {}", stats.window_length, config.window_length));
+        }
+        if stats.n != 0 && stats.n as usize != len + 1 {
+            return Err(format!("n {} does not match {} intervals", stats.n, len));
+        }
+        if stats.n == 0 && len != 0 {
+            return Err(format!("{} intervals without any arrival", len));
+        }
+        if stats.last_accepted_at > stats.last_arrived_at {
+            return Err("last_accepted_at is after last_arrived_at".to_string());
+        }
+        if stats.last_arrived_at > Local::now() + CLOCK_TOLERANCE {
+            return Err(format!("last_arrived_at {} is in the future", stats.last_arrived_at));
+        }
+        if let Some((_, (variance, mean))) = stats.moments_cache.get() {
+            if variance.is_nan() || mean.is_nan() {
+                return Err("cached moments are NaN".to_string());
+            }
+        }
+        if stats.arrival_phis.len() > stats.flap_history_capacity.unwrap_or(0) {
+            return Err(format!("{} recorded arrival phis", stats.arrival_phis.len()));
+        }
+        if stats.arrival_phis.iter().any(|(_, phi)| !(0. ..=config.max_phi).contains(phi)) {
+            return Err("recorded arrival phi outside of [0, max_phi]".to_string());
+        }
+        Ok(())
+    }
+
+    /// Current configuration of the detector, or `PhiError::Poisoned` if the lock is poisoned
+    /// and the configuration asks for `PoisonPolicy::Fail`.
+    pub fn config(&self) -> Result<DetectorConfig, PhiError> {
+        self.read_config()
+    }
+
+    /// Current configuration, or `PhiError::Poisoned` if the lock is poisoned and the
+    /// configuration asks for `PoisonPolicy::Fail`.
+    fn read_config(&self) -> Result<DetectorConfig, PhiError> {
+        match self.config.read() {
+            Ok(config) => Ok(config.clone()),
+            Err(poisoned) => poisoned.into_inner().clone().recover_poisoned(),
+        }
+    }
+
+    /// Validate and apply a new configuration without dropping the learned window.
+    /// Each phi evaluation sees either the old or the new configuration as a whole.
+    pub async fn apply_config(&self, config: DetectorConfig) -> Result<(), PhiError> {
+        config.validate()?;
+        let mut stats = self.statistics.write().await;
+        if stats.window_length != config.window_length {
+            *stats = stats.clone().with_window_length(config.window_length);
+        }
+        stats.configure(&config);
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = config;
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Insert a heartbeat that ends a gap of `missed` heartbeats known to be lost in transit.
+    /// The sender is assumed alive throughout, so the gap is recorded according to the
+    /// detector's `GapHandling` instead of as a single long interval. Fails with
+    /// `PhiError::UnorderedArrivals` if it arrived before the last heartbeat and with
+    /// `PhiError::IntervalOverflow` if a split interval exceeds max_interval.
+    pub async fn insert_gap(&self, arrived_at: DateTime<Local>, missed: u32) -> Result<(), Box<dyn Error>> {
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        if stats.n != 0 && config.gap_handling == GapHandling::Split {
+            let interval_ms = arrived_at.sub(stats.last_accepted_at).num_milliseconds() / (missed as i64 + 1);
+            if interval_ms > config.max_interval.num_milliseconds() {
+                return Err(PhiError::IntervalOverflow {
+                    interval_ms,
+                    max_ms: config.max_interval.num_milliseconds(),
+                }.into());
+            }
+        }
+        stats.record_arrival_phi(arrived_at, &config);
+        stats.insert_gap(arrived_at, missed, config.gap_handling)?;
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Start from a deep copy of source's learned statistics, trimmed to this detector's window.
+    /// The copy is independent, later inserts into either detector do not affect the other.
+    pub async fn warm_start_from(&self, source: &Detector) -> Result<(), Box<dyn Error>> {
+        let snapshot = source.statistics.read().await.clone();
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        *stats = snapshot.with_window_length(config.window_length);
+        stats.configure(&config);
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Atomically swap in statistics recomputed elsewhere, e.g. from a corrected data set,
+    /// trimmed to this detector's window. The previous statistics and smoothed phi are dropped.
+    pub async fn replace_statistics(&self, new: Statistics) -> Result<(), Box<dyn Error>> {
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        *stats = new.with_window_length(config.window_length);
+        stats.configure(&config);
+        *self.smoothed_phi.lock().await = None;
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Forget every heartbeat and the smoothed phi, keeping the configuration, as if the Detector
+    /// was just created. Readers observe either the previous state or the cleared one.
+    pub async fn reset(&self) -> Result<(), Box<dyn Error>> {
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        *stats = Statistics::new(config.window_length);
+        stats.configure(&config);
+        *self.smoothed_phi.lock().await = None;
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Replace the window with window_length intervals of target_interval_ms, the last heartbeat
+    /// arriving now, as if trained on a perfectly regular sender. Keeps the configuration; with
+    /// zero variance, min_std_deviation sets how quickly phi rises.
+    pub async fn prewarm(&self, target_interval_ms: u64) -> Result<(), Box<dyn Error>> {
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        *stats = Statistics::new(config.window_length);
+        stats.configure(&config);
+        stats.n = 1;
+        for _ in 0..config.window_length {
+            stats.push_interval(target_interval_ms);
+        }
+        *self.smoothed_phi.lock().await = None;
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Force phi to max_phi on out-of-band evidence that the node is down, such as a connection
+    /// reset or a shutdown notice, until the next accepted heartbeat.
+    pub async fn mark_down(&self) {
+        let mut stats = self.statistics.write().await;
+        stats.marked_down = true;
+        self.notify_changed();
+    }
+
+    /// Number of intervals counted as outages instead of being recorded, see `OutageHandling`.
+    pub async fn outage_count(&self) -> u64 {
+        self.statistics.read().await.outages
+    }
+
+    /// Number of heartbeats that only refreshed `last_arrived_at` because they arrived
+    /// within `min_accept_interval` of the previously accepted one.
+    pub async fn rate_limited(&self) -> u64 {
+        self.statistics.read().await.rate_limited
+    }
+
+    /// Insert a heartbeat carrying the sender's incarnation, which increases whenever it restarts.
+    /// A higher incarnation than the current one resets the window, keeping the configuration,
+    /// and returns true. Heartbeats from an older incarnation are rejected as stale.
+    pub async fn insert_with_incarnation(&self, arrived_at: DateTime<Local>, incarnation: u64) -> Result<bool, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        let restarted = match stats.incarnation {
+            Some(current) if incarnation < current => {
+                return Err(PhiError::StaleIncarnation { current, received: incarnation }.into());
+            }
+            Some(current) => incarnation > current,
+            None => false,
+        };
+        if restarted {
+            stats.restart();
+        }
+        stats.incarnation = Some(incarnation);
+        stats.record_arrival_phi(arrived_at, &config);
+        stats.insert(arrived_at);
+        self.notify_changed();
+        Ok(restarted)
+    }
+
+    /// Insert a heartbeat carrying the application-level health the node reported. The timing is
+    /// recorded either way, unhealthy heartbeats are also counted and raise `adjusted_phi`.
+    pub async fn insert_with_status(&self, arrived_at: DateTime<Local>, healthy: bool) -> Result<(), Box<dyn Error>> {
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        stats.record_arrival_phi(arrived_at, &config);
+        stats.insert(arrived_at);
+        if !healthy {
+            stats.unhealthy += 1;
+        }
+        if stats.recent_statuses.len() == STATUS_HISTORY_LENGTH {
+            stats.recent_statuses.pop_front();
+        }
+        stats.recent_statuses.push_back(healthy);
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Apply events in order, inserting heartbeats and evaluating phi for queries. Returns one
+    /// entry per query, None for queries made before the first heartbeat.
+    pub async fn replay(&self, events: &[Event]) -> Result<Vec<Option<f64>>, Box<dyn Error>> {
+        let mut phis = vec![];
+        for event in events {
+            match *event {
+                Event::Heartbeat(arrived_at) => self.insert(arrived_at).await?,
+                Event::Query(t) => {
+                    let config = self.read_config()?;
+                    let stats = self.statistics.read().await;
+                    phis.push((stats.n != 0).then(|| self.phi_of(&stats, t, &config)));
+                }
+            }
+        }
+        Ok(phis)
+    }
+
+    /// Phi most recently computed by `phi`, `status` or `severity`, with the time it was computed
+    /// for. A lock-free read that never recomputes.
+    pub fn last_computed_phi(&self) -> Option<(f64, DateTime<Local>)> {
+        self.last_phi.load()
+    }
+
+    /// Run callback with the final summary once the Detector shuts down, e.g. to flush metrics.
+    pub fn on_shutdown<F>(&self, callback: F)
+        where F: FnOnce(&DetectorSummary) + Send + Sync + 'static {
+        self.shutdown_hooks.0.lock().unwrap_or_else(PoisonError::into_inner).push(Box::new(callback));
+    }
+
+    /// Consume the Detector, running the shutdown callbacks with its final summary.
+    pub async fn shutdown(self) -> DetectorSummary {
+        self.finish().await
+    }
+
+    /// Final summary, running the shutdown callbacks registered so far. Also used for detectors
+    /// that may still be shared, such as the ones removed from a registry.
+    pub(crate) async fn finish(&self) -> DetectorSummary {
+        let summary = {
+            let stats = self.statistics.read().await;
+            let moments = match self.read_config() {
+                Ok(config) if !stats.arrival_intervals.is_empty() => Some(stats.moments(config.variance_reference)),
+                _ => None,
+            };
+            DetectorSummary {
+                heartbeats: stats.heartbeats_recorded(),
+                sample_count: stats.arrival_intervals.len(),
+                mean_ms: moments.map(|(_, mean)| mean),
+                std_dev_ms: moments.map(|(variance, _)| variance.sqrt()),
+                last_arrived_at: (stats.n != 0).then_some(stats.last_arrived_at),
+                last_phi: self.last_phi.load().map(|(phi, _)| phi),
+            }
+        };
+        let hooks = std::mem::take(&mut *self.shutdown_hooks.0.lock().unwrap_or_else(PoisonError::into_inner));
+        for hook in hooks {
+            hook(&summary);
+        }
+        summary
+    }
+
+    /// Number of heartbeats that reported the node unhealthy.
+    pub async fn unhealthy_heartbeats(&self) -> u64 {
+        self.statistics.read().await.unhealthy
+    }
+
+    /// Phi at t raised by the share of unhealthy heartbeats among the last STATUS_HISTORY_LENGTH
+    /// status-carrying ones, times the threshold. A node reporting only unhealthy heartbeats is
+    /// therefore suspected even when its timing is perfect. Capped at max_phi.
+    pub async fn adjusted_phi(&self, t: DateTime<Local>) -> Result<f64, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        let phi = stats.phi_at(t, &config);
+        if stats.recent_statuses.is_empty() {
+            return Ok(phi);
+        }
+        let unhealthy = stats.recent_statuses.iter().filter(|healthy| !**healthy).count();
+        let ratio = unhealthy as f64 / stats.recent_statuses.len() as f64;
+        Ok((phi + ratio * config.threshold).min(config.max_phi))
+    }
+
+    /// Number of times a higher incarnation reset the window.
+    pub async fn restarts(&self) -> u64 {
+        self.statistics.read().await.restarts
+    }
+
+    /// Copy of the arrival log, oldest first. Empty unless enabled with `DetectorBuilder::with_arrival_log`.
+    pub async fn arrival_log(&self) -> Vec<ArrivalLogEntry> {
+        self.statistics.read().await.arrival_log.iter().copied().collect()
+    }
+
+    /// Write the window intervals as CSV with an `index,interval_ms` header, oldest first.
+    pub async fn export_csv(&self, mut writer: impl std::io::Write) -> Result<(), Box<dyn Error>> {
+        let stats = self.statistics.read().await;
+        writeln!(writer, "index,interval_ms")?;
+        for (i, v) in stats.arrival_intervals.iter().enumerate() {
+            writeln!(writer, "{i},{v}")?;
+        }
+        Ok(())
+    }
+
+    /// Measure the next interval from now while keeping the learned window, e.g. after a
+    /// deliberate reconfiguration of the sender's cadence. Also restarts the startup grace.
+    pub async fn rebase_clock(&self) {
+        let mut stats = self.statistics.write().await;
+        let now = Local::now();
+        stats.last_arrived_at = now;
+        stats.last_accepted_at = now;
+        stats.started_at = now;
+        self.notify_changed();
+    }
+
+    /// Insert a heartbeat, failing with `PhiError::IntervalOverflow` and leaving the window
+    /// untouched if it arrived more than max_interval after the last accepted one.
+    pub async fn insert_checked(&self, arrived_at: DateTime<Local>) -> Result<(), Box<dyn Error>> {
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        if stats.n != 0 {
+            let interval = arrived_at.sub(stats.last_accepted_at);
+            if interval > config.max_interval {
+                return Err(PhiError::IntervalOverflow {
+                    interval_ms: interval.num_milliseconds(),
+                    max_ms: config.max_interval.num_milliseconds(),
+                }.into());
+            }
+        }
+        stats.record_arrival_phi(arrived_at, &config);
+        stats.insert(arrived_at);
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Insert a heartbeat `ms` milliseconds after the last one, returning its arrival time.
+    /// Meant for tests and simulations that would otherwise build timestamps by hand.
+    pub async fn insert_after_ms(&self, ms: u64) -> Result<DateTime<Local>, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        let arrived_at = stats.last_arrived_at + TimeDelta::milliseconds(ms as i64);
+        stats.record_arrival_phi(arrived_at, &config);
+        stats.insert(arrived_at);
+        self.notify_changed();
+        Ok(arrived_at)
+    }
+
+    /// Insert a heartbeat interval after the last one, as measured by the caller, returning its
+    /// arrival time. The arrival time advances by the exact interval, which the window records
+    /// in whole ms like any other.
+    pub async fn insert_duration(&self, interval: std::time::Duration) -> Result<DateTime<Local>, Box<dyn Error>> {
+        let interval = TimeDelta::from_std(interval)?;
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        let arrived_at = stats.last_arrived_at + interval;
+        stats.record_arrival_phi(arrived_at, &config);
+        stats.insert(arrived_at);
+        self.notify_changed();
+        Ok(arrived_at)
+    }
+
+    /// Interval (in ms) at percentile p in [0, 100] of the window, using the nearest-rank method.
+    pub async fn percentile(&self, p: f64) -> Result<u64, Box<dyn Error>> {
+        self.percentile_into(p, &mut vec![]).await
+    }
+
+    /// Same as `percentile`, sorting into the caller-provided scratch buffer so that repeated
+    /// calls in a hot loop reuse its allocation.
+    pub async fn percentile_into(&self, p: f64, scratch: &mut Vec<u64>) -> Result<u64, Box<dyn Error>> {
+        if !(0. ..=100.).contains(&p) {
+            return Err(PhiError::InvalidPercentile(p).into());
+        }
+        scratch.clear();
+        scratch.extend(self.statistics.read().await.arrival_intervals.iter().map(|v| interval_ms(*v)));
+        if scratch.is_empty() {
+            return Err(PhiError::EmptyWindow.into());
+        }
+        scratch.sort_unstable();
+        let rank = (p / 100. * scratch.len() as f64).ceil() as usize;
+        Ok(scratch[rank.max(1) - 1])
+    }
+
+    /// Window intervals in ms in ascending order, e.g. for custom histograms. Copied under the
+    /// read lock and sorted once it is released.
+    pub async fn sorted_intervals(&self) -> Vec<u64> {
+        let mut intervals: Vec<u64> = self.statistics.read().await.arrival_intervals.iter().map(|v| interval_ms(*v)).collect();
+        intervals.sort_unstable();
+        intervals
+    }
+
+    /// Whether the window interval at quantile q in [0, 1] is at most budget_ms, e.g. to gate on
+    /// a p95 SLO. Uses the nearest-rank method of `percentile`.
+    pub async fn quantile_within(&self, q: f64, budget_ms: u64) -> Result<bool, Box<dyn Error>> {
+        if !(0. ..=1.).contains(&q) {
+            return Err(PhiError::InvalidQuantile(q).into());
+        }
+        Ok(self.percentile(q * 100.).await? <= budget_ms)
+    }
+
+    /// Family and parameters of the distribution phi is currently computed from.
+    pub async fn distribution_params(&self) -> Result<DistributionParams, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        if stats.arrival_intervals.is_empty() && stats.seeded_moments.is_none() {
+            return Err(PhiError::EmptyWindow.into());
+        }
+        let (sigma_sq, mean) = stats.moments(config.variance_reference);
+        let std_dev = sigma_sq.sqrt().max(config.min_std_deviation.num_milliseconds() as f64);
+        let family = match config.distribution {
+            Distribution::Kde { bandwidth } if !stats.arrival_intervals.is_empty() => DistributionFamily::Kde {
+                bandwidth: kde_bandwidth(&stats.arrival_intervals, bandwidth, &config),
+            },
+            Distribution::Gamma if mean > 0. && std_dev > 0. => DistributionFamily::Gamma {
+                shape: mean * mean / (std_dev * std_dev),
+                rate: mean / (std_dev * std_dev),
+            },
+            Distribution::StudentT if stats.arrival_intervals.len() >= 2 && std_dev > 0. => DistributionFamily::StudentT {
+                degrees_of_freedom: (stats.arrival_intervals.len() - 1) as f64,
+            },
+            _ => DistributionFamily::Normal,
+        };
+        Ok(DistributionParams { family, mean, std_dev })
+    }
+
+    /// Interval (in ms) at quantile q in [0, 1] over every interval recorded since creation,
+    /// estimated by a t-digest that stays accurate at the tails.
+    #[cfg(feature = "tdigest")]
+    pub async fn quantile(&self, q: f64) -> Result<f64, Box<dyn Error>> {
+        if !(0. ..=1.).contains(&q) {
+            return Err(PhiError::InvalidQuantile(q).into());
+        }
+        let mut digest = self.statistics.read().await.digest.clone();
+        digest.flush();
+        Ok(digest.estimate_quantile(q).ok_or(PhiError::EmptyWindow)?)
+    }
+
+    /// Whether the recent half of the window has significantly longer intervals than the older
+    /// half, by a one-sided Welch test at the given significance in (0, 1). Uses the normal
+    /// approximation of the test statistic, so each half needs at least 4 intervals.
+    pub async fn change_point_detected(&self, significance: f64) -> Result<bool, Box<dyn Error>> {
+        const MIN_HALF: usize = 4;
+        if !(significance > 0. && significance < 1.) {
+            return Err(PhiError::InvalidConfig("significance must be in (0, 1)".to_string()).into());
+        }
+        let stats = self.statistics.read().await;
+        let half = stats.arrival_intervals.len() / 2;
+        if half < MIN_HALF {
+            return Err(PhiError::InsufficientSamples { available: stats.arrival_intervals.len(), required: 2 * MIN_HALF }.into());
+        }
+        let older = &stats.arrival_intervals[stats.arrival_intervals.len() - 2 * half..][..half];
+        let recent = &stats.arrival_intervals[stats.arrival_intervals.len() - half..];
+        let (older_mean, recent_mean) = (mean_of(older), mean_of(recent));
+        // unbiased variances of the mean of each half
+        let correction = half as f64 / (half as f64 - 1.);
+        let standard_error = ((variance_of(older, older_mean) + variance_of(recent, recent_mean)) * correction / half as f64).sqrt();
+        if standard_error == 0. {
+            return Ok(recent_mean > older_mean);
+        }
+        let z = (recent_mean - older_mean) / standard_error;
+        Ok(normal_tail(z, 0., 1.) < significance)
+    }
+
+    /// Count, sum, min and max of the window in a single pass, without sorting or computing
+    /// the variance. The sum saturates at `u64::MAX`.
+    pub async fn intervals_summary(&self) -> Result<IntervalsSummary, Box<dyn Error>> {
+        let stats = self.statistics.read().await;
+        let mut intervals = stats.arrival_intervals.iter().map(|v| interval_ms(*v));
+        let first = intervals.next().ok_or(PhiError::EmptyWindow)?;
+        let mut summary = IntervalsSummary { count: 1, sum_ms: first, min_ms: first, max_ms: first };
+        for v in intervals {
+            summary.count += 1;
+            summary.sum_ms = summary.sum_ms.saturating_add(v);
+            summary.min_ms = summary.min_ms.min(v);
+            summary.max_ms = summary.max_ms.max(v);
+        }
+        Ok(summary)
+    }
+
+    /// Mean absolute deviation of the window intervals from their mean in ms, i.e. the
+    /// typical jitter. Independent of the phi computation.
+    pub async fn mean_absolute_deviation(&self) -> Result<f64, Box<dyn Error>> {
+        let stats = self.statistics.read().await;
+        if stats.arrival_intervals.is_empty() {
+            return Err(PhiError::EmptyWindow.into());
+        }
+        let mean = mean_of(&stats.arrival_intervals);
+        let sum: f64 = stats.arrival_intervals.iter().map(|v| (*v as f64 - mean).abs()).sum();
+        Ok(sum / stats.arrival_intervals.len() as f64)
+    }
+
+    /// Coefficient of variation of the window intervals, their standard deviation over their
+    /// mean. Near 0 for a regular sender and high for one sending bursts between silences,
+    /// which a plausible mean can hide. 0 when all intervals are equal, including all 0.
+    pub async fn burstiness(&self) -> Result<f64, Box<dyn Error>> {
+        let stats = self.statistics.read().await;
+        if stats.arrival_intervals.is_empty() {
+            return Err(PhiError::EmptyWindow.into());
+        }
+        let mean = mean_of(&stats.arrival_intervals);
+        let std_dev = variance_of(&stats.arrival_intervals, mean).sqrt();
+        Ok(if std_dev == 0. { 0. } else { std_dev / mean })
+    }
+
+    /// Number of times phi crossed threshold, in either direction, within the window up to now.
+    /// A breach ended by a heartbeat within the window counts twice, one still ongoing at now
+    /// once if it began within the window. Past breaches are only known from the flap history,
+    /// so this fails with `PhiError::InvalidConfig` unless `DetectorBuilder::with_flap_history`
+    /// enabled it.
+    pub async fn flap_count_at(&self, now: DateTime<Local>, window: TimeDelta, threshold: f64) -> Result<u32, Box<dyn Error>> {
+        let config = self.read_config()?;
+        if config.flap_history.is_none() {
+            return Err(PhiError::InvalidConfig("flap history disabled".to_string()).into());
+        }
+        let stats = self.statistics.read().await;
+        let since = now.sub(window);
+        let ended = stats.arrival_phis.iter()
+            .filter(|(arrived_at, phi)| (since..=now).contains(arrived_at) && *phi > threshold)
+            .count() as u32;
+        let ongoing = stats.suspected_at(threshold, &config).is_some_and(|at| (since..=now).contains(&at));
+        Ok(2 * ended + u32::from(ongoing))
+    }
+
+    /// Flap count up to the current time, see `flap_count_at`.
+    pub async fn flap_count(&self, window: TimeDelta, threshold: f64) -> Result<u32, Box<dyn Error>> {
+        self.flap_count_at(Local::now(), window, threshold).await
+    }
+
+    /// Bitfield of the last min(n, 64) intervals where bit i is set when the i-th most recent
+    /// interval was on time, i.e. within mean ± standard deviation of the window (measured
+    /// around the configured variance reference). Fails with `PhiError::InsufficientSamples`
+    /// while the window is empty.
+    pub async fn recent_health_bits(&self, n: usize) -> Result<u64, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        let intervals = &stats.arrival_intervals;
+        if intervals.is_empty() {
+            return Err(PhiError::InsufficientSamples { available: 0, required: 1 }.into());
+        }
+        let (variance, mean) = stats.moments(config.variance_reference);
+        let sigma = variance.sqrt();
+        let mut bits = 0;
+        for (i, v) in intervals.iter().rev().take(n.min(64)).enumerate() {
+            if (*v as f64 - mean).abs() <= sigma {
+                bits |= 1 << i;
+            }
+        }
+        Ok(bits)
+    }
+
+    /// Human-readable explanation of the suspicion at t, such as
+    /// "Node last seen 1.2s ago; typical interval 1.7s ± 1.9s; phi 0.4 (healthy)."
+    pub async fn describe(&self, t: DateTime<Local>, threshold: f64) -> Result<String, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        let (sigma_sq, mean) = stats.phi_moments(&config);
+        let std_dev = snap_std_dev(sigma_sq.sqrt().max(config.min_std_deviation.num_milliseconds() as f64), &config);
+        let last_seen = t.sub(stats.last_arrived_at).num_milliseconds() as f64 / 1000.;
+        let phi = self.phi_of(&stats, t, &config);
+        let verdict = if phi > threshold { "suspected" } else { "healthy" };
+        Ok(format!("Node last seen {:.1}s ago; typical interval {:.1}s ± {:.1}s; phi {:.1} ({}).",
+                   last_seen, mean / 1000., std_dev / 1000., phi, verdict))
+    }
+
+    /// Best-effort insert that never awaits: returns `TryError::WouldBlock` instead of waiting
+    /// when the statistics are locked, in which case the heartbeat is not recorded.
+    pub fn try_insert(&self, arrived_at: DateTime<Local>) -> Result<(), TryError> {
+        let config = self.try_read_config()?;
+        let mut stats = self.statistics.try_write().map_err(|_| TryError::WouldBlock)?;
+        stats.record_arrival_phi(arrived_at, &config);
+        stats.insert(arrived_at);
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Current configuration without blocking on the lock, honoring the `PoisonPolicy`.
+    fn try_read_config(&self) -> Result<DetectorConfig, TryError> {
+        match self.config.try_read() {
+            Ok(config) => Ok(config.clone()),
+            Err(TryLockError::WouldBlock) => Err(TryError::WouldBlock),
+            Err(TryLockError::Poisoned(poisoned)) => {
+                poisoned.into_inner().clone().recover_poisoned().map_err(|_| TryError::Poisoned)
+            }
+        }
+    }
+
+    /// Best-effort phi that never awaits: returns `TryError::WouldBlock` instead of waiting
+    /// when the statistics or the configuration are being written.
+    pub fn try_phi(&self, t: DateTime<Local>) -> Result<f64, TryError> {
+        let config = self.try_read_config()?;
+        let stats = self.statistics.try_read().map_err(|_| TryError::WouldBlock)?;
+        Ok(stats.phi_at(t, &config))
+    }
+
+    /// Phi once `elapsed` has passed since the last heartbeat.
+    pub async fn phi_for_elapsed(&self, elapsed: TimeDelta) -> Result<f64, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        Ok(stats.phi_at(stats.last_arrived_at.add(elapsed), &config))
+    }
+
+    /// (offset, phi) pairs for offsets in ms from the last heartbeat, from from_offset_ms to
+    /// to_offset_ms every step_ms (at least 1). Offsets before the last heartbeat yield 0.
+    pub async fn phi_timeline(&self, from_offset_ms: i64, to_offset_ms: i64, step_ms: u64) -> Result<Vec<(i64, f64)>, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        let step = step_ms.max(1) as usize;
+        Ok((from_offset_ms..=to_offset_ms).step_by(step)
+            .map(|offset| match offset {
+                ..=-1 => (offset, 0.),
+                _ => (offset, stats.phi_at(stats.last_arrived_at.add(TimeDelta::milliseconds(offset)), &config)),
+            })
+            .collect())
+    }
+
+    /// Phi at t with the number of window intervals it was computed from, read under one lock.
+    pub async fn phi_with_n(&self, t: DateTime<Local>) -> Result<(f64, usize), Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        Ok((self.phi_of(&stats, t, &config), stats.arrival_intervals.len()))
+    }
+
+    /// Phi at t with the estimated standard deviation scaled by sigma_multiplier before the tail
+    /// probability, e.g. above 1 to be more forgiving of a small sample. Without effect on
+    /// `Distribution::Kde`. Fails with `PhiError::InvalidConfig` unless the multiplier is positive.
+    pub async fn phi_conservative(&self, t: DateTime<Local>, sigma_multiplier: f64) -> Result<f64, Box<dyn Error>> {
+        if !sigma_multiplier.is_finite() || sigma_multiplier <= 0. {
+            return Err(PhiError::InvalidConfig("sigma_multiplier must be finite and positive".to_string()).into());
+        }
+        let config = self.read_config()?;
+        Ok(self.statistics.read().await.phi_at_scaled(t, sigma_multiplier, &config))
+    }
+
+    /// Slope of phi per ms of delay at an elapsed time equal to the mean interval, by central
+    /// difference over 1 ms either side. Steeper means quicker to suspect a late heartbeat.
+    pub async fn sensitivity(&self) -> Result<f64, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        if stats.arrival_intervals.is_empty() {
+            return Err(PhiError::EmptyWindow.into());
+        }
+        let mean = stats.moments(config.variance_reference).1.round() as i64;
+        let phi = |ms: i64| stats.phi_at(stats.last_arrived_at.add(TimeDelta::milliseconds(ms)), &config);
+        Ok((phi(mean + 1) - phi(mean - 1)) / 2.)
+    }
+
+    /// Reconnection backoff for a peer, base scaled by phi at now and capped at max. Phi at or
+    /// below 1 yields base.
+    pub async fn suggested_backoff(&self, now: DateTime<Local>, base: std::time::Duration, max: std::time::Duration) -> Result<std::time::Duration, Box<dyn Error>> {
+        let phi = self.phi(now).await?;
+        let backoff = std::time::Duration::try_from_secs_f64(base.as_secs_f64() * phi.max(1.));
+        Ok(backoff.map_or(max, |backoff| backoff.min(max)))
+    }
+
+    /// Phi at each of ts, evaluated against a single snapshot of the window.
+    pub async fn phi_batch(&self, ts: &[DateTime<Local>]) -> Result<Vec<f64>, Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        Ok(ts.iter().map(|t| stats.phi_at(*t, &config)).collect())
+    }
+
+    /// Phi at t computed over only the trailing k intervals of the window, failing with
+    /// `PhiError::InsufficientSamples` when fewer than min_samples of them are available.
+    pub async fn phi_recent(&self, t: DateTime<Local>, k: usize) -> Result<f64, Box<dyn Error>> {
+        let config = self.read_config()?;
+        Ok(self.statistics.read().await.phi_recent_at(t, k, &config)?)
+    }
+
+    /// Resolve with phi and the time it happened once phi exceeds threshold while warmed up,
+    /// immediately if it already does. Time follows tokio's clock from the moment of the call, so paused time works.
+    /// Instead of polling, the future sleeps until the phi predicted from the current window
+    /// crosses threshold and re-evaluates on wake-up or whenever a heartbeat or config change
+    /// arrives. It spawns nothing and holds no lock while waiting, so it can be cancelled by
+    /// dropping it, e.g. as a losing `select!` branch.
+    pub async fn wait_until_suspected(&self, threshold: f64) -> Result<(f64, DateTime<Local>), Box<dyn Error>> {
+        let anchor = ClockAnchor::now();
+        loop {
+            let changed = self.changed.notified();
+            let deadline = {
+                let config = self.read_config()?;
+                let stats = self.statistics.read().await;
+                let now = anchor.to_date_time(Instant::now());
+                let phi = stats.phi_at(now, &config);
+                if stats.warmed_up(&config) && phi > threshold {
+                    return Ok((phi, now));
+                }
+                stats.suspected_at(threshold, &config)
+                    .map(|at| anchor.to_instant(at))
+            };
+            match deadline {
+                Some(deadline) => tokio::select! {
+                    _ = tokio::time::sleep_until(deadline) => {}
+                    _ = changed => {}
+                },
+                None => changed.await,
+            }
+        }
+    }
+
+    /// Exponentially smoothed phi across successive calls. Each call folds phi at `t` into the
+    /// moving average and returns the updated value.
+    pub async fn smoothed_phi(&self, t: DateTime<Local>) -> Result<f64, Box<dyn Error>> {
+        let config = self.read_config()?;
+        // smoothed_phi is locked after the statistics, like in reset, so that a reset cannot slip in between
+        let stats = self.statistics.read().await;
+        let phi = self.phi_of(&stats, t, &config);
+        let mut smoothed = self.smoothed_phi.lock().await;
+        let value = match *smoothed {
+            Some(prev) if prev.is_finite() && phi.is_finite() => {
+                config.phi_smoothing * phi + (1. - config.phi_smoothing) * prev
+            }
+            _ => phi,
+        };
+        *smoothed = Some(value);
+        Ok(value)
+    }
+}
+
+impl Statistics {
+    /// New Statistics instance with window_length.
+    pub fn new(window_length: u32) -> Self {
+        Statistics::starting_at(window_length, Local::now())
+    }
+
+    /// Statistics equal to inserting the sorted arrivals one by one with oldest-first eviction,
+    /// computed in a single pass over the trailing window_length intervals.
+    pub fn rebuild(arrivals: &[DateTime<Local>], window_length: u32) -> Result<Statistics, PhiError> {
+        if let Some(index) = (1..arrivals.len()).find(|&i| arrivals[i] < arrivals[i - 1]) {
+            return Err(PhiError::UnorderedArrivals { index });
+        }
+        let mut statistics = Statistics::new(window_length);
+        let Some(last_arrived_at) = arrivals.last() else {
+            return Ok(statistics);
+        };
+        let first = arrivals.len().saturating_sub(window_length as usize + 1);
+        statistics.arrival_intervals = arrivals[first..].windows(2)
+            .map(|pair| to_interval(pair[1].sub(pair[0]).num_milliseconds() as u64))
+            .collect();
+        statistics.last_arrived_at = *last_arrived_at;
+        statistics.last_accepted_at = *last_arrived_at;
+        statistics.n = statistics.arrival_intervals.len() as u32 + 1;
+        Ok(statistics)
+    }
+
+    /// Statistics seeded with a known mean and variance in milliseconds instead of intervals.
+    /// The seeded moments are used until the first interval is recorded.
+    pub fn from_moments(window_length: u32, mean: f64, variance: f64, last_arrived_at: DateTime<Local>) -> Result<Statistics, PhiError> {
+        if !mean.is_finite() || mean < 0. || !variance.is_finite() || variance < 0. {
+            return Err(PhiError::InvalidConfig("mean and variance must be finite and not negative".to_string()));
+        }
+        let mut statistics = Statistics::new(window_length);
+        statistics.last_arrived_at = last_arrived_at;
+        statistics.last_accepted_at = last_arrived_at;
+        statistics.n = 1;
+        statistics.seeded_moments = Some((mean, variance));
+        Ok(statistics)
+    }
+
+    /// Earliest time, to the ms, at which phi exceeds threshold if no further heartbeat arrives.
+    /// Relies on phi not decreasing with elapsed time. None if not warmed up or phi never gets there.
+    fn suspected_at(&self, threshold: f64, config: &DetectorConfig) -> Option<DateTime<Local>> {
+        const MAX_ELAPSED_MS: i64 = 1 << 40;
+        if !self.warmed_up(config) {
+            return None;
+        }
+        let exceeds = |ms: i64| self.phi_at(self.last_arrived_at + TimeDelta::milliseconds(ms), config) > threshold;
+        let mut high = 1;
+        while !exceeds(high) {
+            if high >= MAX_ELAPSED_MS {
+                return None;
+            }
+            high *= 2;
+        }
+        let mut low = high / 2;
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if exceeds(mid) {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        Some(self.last_arrived_at + TimeDelta::milliseconds(high))
+    }
+}
+
+impl<T: Timestamp> Statistics<T> {
+    /// New Statistics instance with window_length, created at now.
+    pub fn starting_at(window_length: u32, now: T) -> Self {
+        Statistics {
+            arrival_intervals: Intervals::new(),
+            last_arrived_at: now,
+            window_length,
+            n: 0,
+            eviction_policy: EvictionPolicy::default(),
+            last_accepted_at: now,
+            min_accept_interval: TimeDelta::milliseconds(0),
+            min_heartbeat_spacing_ms: None,
+            rate_limited: 0,
+            outage_handling: OutageHandling::default(),
+            outages: 0,
+            decay_kernel: DecayKernel::default(),
+            intervals_seen: 0,
+            #[cfg(feature = "tdigest")]
+            digest: tdigest::TDigest::new_with_size(TDIGEST_SIZE),
+            incarnation: None,
+            restarts: 0,
+            unhealthy: 0,
+            recent_statuses: VecDeque::new(),
+            seeded_moments: None,
+            started_at: now,
+            flap_history_capacity: None,
+            arrival_phis: VecDeque::new(),
+            recovered_at: None,
+            marked_down: false,
+            arrival_log_capacity: None,
+            arrival_log: VecDeque::new(),
+            moments_cache: OnceLock::new(),
+            #[cfg(test)]
+            moments_computed: Arc::default(),
+        }
+    }
+
+    /// Remember the phi reached right before a heartbeat arriving at arrived_at, computed only
+    /// when the flap history or the probation policy needs it.
+    fn record_arrival_phi(&mut self, arrived_at: T, config: &DetectorConfig) {
+        if self.n == 0 || (self.flap_history_capacity.is_none() && config.probation == ProbationPolicy::Disabled) {
+            return;
+        }
+        let phi = self.phi_at(arrived_at, config);
+        if let Some(capacity) = self.flap_history_capacity {
+            if self.arrival_phis.len() == capacity {
+                self.arrival_phis.pop_front();
+            }
+            self.arrival_phis.push_back((arrived_at, phi));
+        }
+        if phi > config.threshold {
+            self.recovered_at = Some((arrived_at, 0));
+        } else if let Some((_, heartbeats)) = &mut self.recovered_at {
+            *heartbeats = heartbeats.saturating_add(1);
+        }
+    }
+
+    /// Heartbeats recorded since creation or the last reset, the first one included.
+    fn heartbeats_recorded(&self) -> u64 {
+        if self.n != 0 { self.intervals_seen + 1 } else { 0 }
+    }
+
+    /// Whether t falls within the probation that followed the last breach of threshold.
+    fn in_probation(&self, t: T, config: &DetectorConfig) -> bool {
+        let Some((recovered_at, heartbeats)) = self.recovered_at else {
+            return false;
+        };
+        match config.probation {
+            ProbationPolicy::Disabled => false,
+            ProbationPolicy::For(duration) => t.delta_ms_since(&recovered_at) < duration.num_milliseconds(),
+            ProbationPolicy::Heartbeats(count) => heartbeats < count,
+        }
+    }
+
+    /// Apply the insert-time settings of config.
+    fn configure(&mut self, config: &DetectorConfig) {
+        self.eviction_policy = config.eviction_policy;
+        self.min_accept_interval = config.min_accept_interval;
+        self.min_heartbeat_spacing_ms = config.min_heartbeat_spacing_ms;
+        self.outage_handling = config.outage_handling;
+        if self.decay_kernel != config.decay_kernel {
+            self.decay_kernel = config.decay_kernel;
+            self.moments_cache = OnceLock::new();
+        }
+        self.arrival_log_capacity = config.arrival_log;
+        let excess = self.arrival_log.len().saturating_sub(config.arrival_log.unwrap_or(0));
+        self.arrival_log.drain(..excess);
+        self.flap_history_capacity = config.flap_history;
+        let excess = self.arrival_phis.len().saturating_sub(config.flap_history.unwrap_or(0));
+        self.arrival_phis.drain(..excess);
+    }
+
+    /// Whether the insert-time settings applied by `configure` are all at their defaults, so
+    /// that inserts only append to an oldest-first window that follows from the trailing
+    /// arrivals alone.
+    fn has_plain_inserts(&self) -> bool {
+        self.eviction_policy == EvictionPolicy::default()
+            && self.min_accept_interval <= TimeDelta::zero()
+            && self.min_heartbeat_spacing_ms.is_none()
+            && self.outage_handling == OutageHandling::default()
+            && self.arrival_log_capacity.is_none()
+    }
+
+    /// Forget the learned window of a previous incarnation.
+    fn restart(&mut self) {
+        self.arrival_intervals.clear();
+        self.moments_cache = OnceLock::new();
+        self.seeded_moments = None;
+        self.arrival_phis.clear();
+        self.recent_statuses.clear();
+        self.n = 0;
+        self.restarts += 1;
+    }
+
+    /// Append to the arrival log if it is enabled.
+    fn log_arrival(&mut self, arrived_at: T, rejection: Option<Rejection>) {
+        let Some(capacity) = self.arrival_log_capacity else {
+            return;
+        };
+        if self.arrival_log.len() == capacity {
+            self.arrival_log.pop_front();
+        }
+        self.arrival_log.push_back(ArrivalLogEntry { arrived_at, rejection });
+    }
+
+    /// Insert heartbeat arrival time in window. Returns whether an interval was recorded, false
+    /// for the first heartbeat, for out-of-order, debounced or rate-limited ones and for
+    /// excluded outages.
+    pub fn insert(&mut self, arrived_at: T) -> bool {
+        self.insert_split(arrived_at, 1)
+    }
+
+    /// Insert heartbeat arrival time ending a gap of `missed` lost heartbeats, returning whether
+    /// intervals were recorded. Fails with `PhiError::UnorderedArrivals` if it arrived before
+    /// the last heartbeat. With `GapHandling::Split` the remainder of the integer division is dropped.
+    pub fn insert_gap(&mut self, arrived_at: T, missed: u32, gap_handling: GapHandling) -> Result<bool, PhiError> {
+        if self.n != 0 && arrived_at.delta_ms_since(&self.last_arrived_at) < 0 {
+            return Err(PhiError::UnorderedArrivals { index: self.heartbeats_recorded() as usize });
+        }
+        let parts = match gap_handling {
+            GapHandling::Split => missed as u64 + 1,
+            GapHandling::Discard if missed == 0 => 1,
+            GapHandling::Discard => 0,
+        };
+        Ok(self.insert_split(arrived_at, parts))
+    }
+
+    /// Accept a heartbeat like `insert` does, recording the time since the last accepted one
+    /// as `parts` equal intervals, none for 0.
+    fn insert_split(&mut self, arrived_at: T, parts: u64) -> bool {
+
+        // insert first element
+        if self.n == 0 {
+            self.last_arrived_at = arrived_at;
+            self.last_accepted_at = arrived_at;
+            self.n += 1;
+            self.marked_down = false;
+            self.log_arrival(arrived_at, None);
+            return false;
+        }
+
+        let since_arrived = arrived_at.delta_ms_since(&self.last_arrived_at);
+        if since_arrived < 0 {
+            self.log_arrival(arrived_at, Some(Rejection::OutOfOrder));
+            return false;
+        }
+
+        if let Some(spacing) = self.min_heartbeat_spacing_ms {
+            if since_arrived < spacing as i64 {
+                self.log_arrival(arrived_at, Some(Rejection::Debounced));
+                return false;
+            }
+        }
+
+        let since_accepted = arrived_at.delta_ms_since(&self.last_accepted_at);
+        if since_accepted < self.min_accept_interval.num_milliseconds() {
+            self.last_arrived_at = arrived_at;
+            self.rate_limited += 1;
+            self.log_arrival(arrived_at, Some(Rejection::RateLimited));
+            return false;
+        }
+        self.log_arrival(arrived_at, None);
+        let arrival_interval = since_accepted as u64 / parts.max(1);
+        let outage = match self.outage_handling {
+            OutageHandling::Exclude(factor) if parts > 0 && (!self.arrival_intervals.is_empty() || self.seeded_moments.is_some()) => {
+                arrival_interval as f64 > factor * self.mean()
+            }
+            _ => false,
+        };
+        if outage {
+            self.outages += 1;
+        } else {
+            for _ in 0..parts {
+                self.push_interval(arrival_interval);
+            }
+        }
+        self.last_arrived_at = arrived_at;
+        self.last_accepted_at = arrived_at;
+        self.marked_down = false;
+        !outage && parts > 0
+    }
+
+    /// Statistics with a different window_length, keeping the most recent intervals that fit.
+    fn with_window_length(mut self, window_length: u32) -> Self {
+        let excess = self.arrival_intervals.len().saturating_sub(window_length as usize);
+        self.arrival_intervals.drain(..excess);
+        self.moments_cache = OnceLock::new();
+        self.window_length = window_length;
+        if self.n != 0 {
+            self.n = self.arrival_intervals.len() as u32 + 1;
+        }
+        self
+    }
+
+    /// Mean of the window intervals, weighted by the decay kernel.
+    fn mean(&self) -> f64 {
+        if let Some((mean, _)) = self.seeded_moments.filter(|_| self.arrival_intervals.is_empty()) {
+            return mean;
+        }
+        match self.decay_kernel {
+            DecayKernel::Uniform => mean_of(&self.arrival_intervals),
+            kernel => self.weighted_mean_of(|v| v, kernel),
+        }
+    }
+
+    /// Variance of the window intervals around mu, weighted by the decay kernel.
+    fn variance_around(&self, mu: f64) -> f64 {
+        if let Some((mean, variance)) = self.seeded_moments.filter(|_| self.arrival_intervals.is_empty()) {
+            return variance + (mean - mu) * (mean - mu);
+        }
+        match self.decay_kernel {
+            DecayKernel::Uniform => variance_of(&self.arrival_intervals, mu),
+            kernel => self.weighted_mean_of(|v| (v - mu) * (v - mu), kernel),
+        }
+    }
+
+    /// Weighted mean of f over the window intervals, oldest first.
+    fn weighted_mean_of(&self, f: impl Fn(f64) -> f64, kernel: DecayKernel) -> f64 {
+        let len = self.arrival_intervals.len();
+        let (mut sum, mut total_weight) = (0., 0.);
+        for (i, v) in self.arrival_intervals.iter().enumerate() {
+            let weight = kernel.weight(len - 1 - i, len);
+            sum += weight * f(*v as f64);
+            total_weight += weight;
+        }
+        if total_weight == 0. { 0. } else { sum / total_weight }
+    }
+
+    /// Variance and center of the window for reference.
+    /// Cached between changes of the window, so that polling phi only evaluates the CDF.
+    fn moments(&self, reference: VarianceReference) -> (f64, f64) {
+        match self.moments_cache.get_or_init(|| (reference, self.compute_moments(reference))) {
+            (cached, moments) if *cached == reference => *moments,
+            _ => self.compute_moments(reference),
+        }
+    }
+
+    /// Variance and center of the window for reference, computed over every interval.
+    fn compute_moments(&self, reference: VarianceReference) -> (f64, f64) {
+        #[cfg(test)]
+        self.moments_computed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let mu = match reference {
+            VarianceReference::SampleMean => self.mean(),
+            VarianceReference::Target(target) => target,
+        };
+        (self.variance_around(mu), mu)
+    }
+
+    /// Phi at t for these statistics under config, within `[0, config.max_phi]`.
+    fn phi_at(&self, t: T, config: &DetectorConfig) -> f64 {
+        self.phi_at_scaled(t, 1., config)
+    }
+
+    /// Phi at t with the standard deviation of the moments scaled by sigma_multiplier.
+    fn phi_at_scaled(&self, t: T, sigma_multiplier: f64, config: &DetectorConfig) -> f64 {
+        if self.marked_down {
+            return config.max_phi;
+        }
+        if self.in_startup_grace(t, config) {
+            return 0.;
+        }
+        if let Distribution::Kde { bandwidth } = config.distribution {
+            if !self.arrival_intervals.is_empty() {
+                return self.phi_for_kde(t, &self.arrival_intervals, bandwidth, config);
+            }
+        }
+        let (sigma_sq, mu) = self.phi_moments(config);
+        let sigma_sq = sigma_sq * sigma_multiplier * sigma_multiplier;
+        self.phi_for_moments(t, sigma_sq, mu, self.arrival_intervals.len(), config)
+    }
+
+    /// Variance and center phi is computed from under config, blended if configured.
+    fn phi_moments(&self, config: &DetectorConfig) -> (f64, f64) {
+        let (mut sigma_sq, mu) = self.moments(config.variance_reference);
+        if let Some(blend) = config.variance_blend {
+            sigma_sq = self.blended_variance(sigma_sq, blend, config.variance_reference);
+        }
+        (sigma_sq, mu)
+    }
+
+    /// Evaluation at now against threshold, phi 0 without heartbeats.
+    fn evaluate(&self, now: T, threshold: f64, config: &DetectorConfig) -> Evaluation {
+        let phi = if self.n != 0 { self.phi_at(now, config) } else { 0. };
+        let sample_count = self.arrival_intervals.len();
+        Evaluation {
+            phi,
+            available: phi <= threshold,
+            sample_count,
+            confidence: (sample_count as f64 / self.window_length as f64).min(1.),
+        }
+    }
+
+    /// Whether a heartbeat arrived and at least min_samples intervals are known.
+    fn warmed_up(&self, config: &DetectorConfig) -> bool {
+        self.n > 0 && self.arrival_intervals.len() >= config.min_samples as usize
+    }
+
+    /// Whether t falls within the startup grace of these statistics.
+    fn in_startup_grace(&self, t: T, config: &DetectorConfig) -> bool {
+        config.startup_grace > TimeDelta::zero() && t.delta_ms_since(&self.started_at) < config.startup_grace.num_milliseconds()
+    }
+
+    /// Phi at t computed over only the trailing k intervals of the window.
+    fn phi_recent_at(&self, t: T, k: usize, config: &DetectorConfig) -> Result<f64, PhiError> {
+        if self.marked_down {
+            return Ok(config.max_phi);
+        }
+        let required = config.min_samples.max(1) as usize;
+        let recent = &self.arrival_intervals[self.arrival_intervals.len().saturating_sub(k)..];
+        if recent.len() < required {
+            return Err(PhiError::InsufficientSamples { available: recent.len(), required });
+        }
+        if self.in_startup_grace(t, config) {
+            return Ok(0.);
+        }
+        if let Distribution::Kde { bandwidth } = config.distribution {
+            return Ok(self.phi_for_kde(t, recent, bandwidth, config));
+        }
+        let mu = match config.variance_reference {
+            VarianceReference::SampleMean => mean_of(recent),
+            VarianceReference::Target(target) => target,
+        };
+        Ok(self.phi_for_moments(t, variance_of(recent, mu), mu, recent.len(), config))
+    }
+
+    /// Whole-window variance long blended with the variance of the trailing intervals, each
+    /// around its own center for reference. Just long while the window is empty.
+    fn blended_variance(&self, long: f64, blend: VarianceBlend, reference: VarianceReference) -> f64 {
+        let recent = &self.arrival_intervals[self.arrival_intervals.len().saturating_sub(blend.short_window as usize)..];
+        if recent.is_empty() {
+            return long;
+        }
+        let mu = match reference {
+            VarianceReference::SampleMean => mean_of(recent),
+            VarianceReference::Target(target) => target,
+        };
+        blend.weight * variance_of(recent, mu) + (1. - blend.weight) * long
+    }
+
+    /// Phi at t for a distribution with variance sigma_sq centered on mu, estimated from samples intervals.
+    fn phi_for_moments(&self, t: T, sigma_sq: f64, mu: f64, samples: usize, config: &DetectorConfig) -> f64 {
+        let sigma = snap_std_dev(sigma_sq.sqrt().max(config.min_std_deviation.num_milliseconds() as f64), config);
+        let elapsed = self.elapsed_ms(t, config);
+        if config.distribution == Distribution::StudentT && samples >= 2 && sigma > 0. {
+            return bound_phi(-ln_student_t_tail((elapsed - mu) / sigma, (samples - 1) as f64) / LN_10, config);
+        }
+        bound_phi(unbounded_phi_from_params(elapsed, mu, sigma, config.distribution), config)
+    }
+
+    /// Phi at t from a Gaussian kernel density estimate over intervals.
+    fn phi_for_kde(&self, t: T, intervals: &[Interval], bandwidth: Bandwidth, config: &DetectorConfig) -> f64 {
+        let h = kde_bandwidth(intervals, bandwidth, config);
+        let x = self.elapsed_ms(t, config);
+        let mut tail = 0.;
+        for v in intervals {
+            tail += normal_tail(x, *v as f64, h) / intervals.len() as f64;
+        }
+        bound_phi(phi_from_tail(tail.clamp(0., 1.)), config)
+    }
+
+    /// Milliseconds since the last arrival at t, beyond the acceptable pause.
+    fn elapsed_ms(&self, t: T, config: &DetectorConfig) -> f64 {
+        (t.delta_ms_since(&self.last_arrived_at) - config.acceptable_pause.num_milliseconds()) as f64
+    }
+
+    /// What changed from these statistics to other, e.g. between two snapshots of a detector.
+    /// Intervals are compared as multisets, so an evicted interval equal to the inserted one
+    /// shows up in neither entered nor left.
+    pub fn diff(&self, other: &Statistics<T>) -> StatisticsDiff {
+        let sorted = |stats: &Statistics<T>| {
+            let mut intervals: Vec<u64> = stats.arrival_intervals.iter().map(|v| interval_ms(*v)).collect();
+            intervals.sort_unstable();
+            intervals
+        };
+        let (before, after) = (sorted(self), sorted(other));
+        let (mut entered, mut left) = (vec![], vec![]);
+        let (mut i, mut j) = (0, 0);
+        while i < before.len() || j < after.len() {
+            if j == after.len() || (i < before.len() && before[i] < after[j]) {
+                left.push(before[i]);
+                i += 1;
+            } else if i == before.len() || after[j] < before[i] {
+                entered.push(after[j]);
+                j += 1;
+            } else {
+                i += 1;
+                j += 1;
+            }
+        }
+        let (mean_before, mean_after) = (self.mean(), other.mean());
+        StatisticsDiff {
+            mean_delta: mean_after - mean_before,
+            variance_delta: other.variance_around(mean_after) - self.variance_around(mean_before),
+            sample_count_delta: other.arrival_intervals.len() as i64 - self.arrival_intervals.len() as i64,
+            entered,
+            left,
+        }
+    }
+
+    /// Position of the interval farthest from the window mean, the first one on ties.
+    fn largest_outlier_position(&self) -> usize {
+        let mean = self.mean();
+        let mut position = 0;
+        let mut largest = f64::NEG_INFINITY;
+        for (i, v) in self.arrival_intervals.iter().enumerate() {
+            let deviation = (*v as f64 - mean).abs();
+            if deviation > largest {
+                largest = deviation;
+                position = i;
+            }
+        }
+        position
+    }
+
+    /// Push an interval into the window, evicting one according to the eviction policy when full.
+    fn push_interval(&mut self, arrival_interval: u64) {
+        let arrival_interval = to_interval(arrival_interval);
+        self.intervals_seen = self.intervals_seen.max(self.arrival_intervals.len() as u64) + 1;
+        #[cfg(feature = "tdigest")]
+        self.digest.push(arrival_interval as f64);
+        if self.n - 1 == self.window_length {
+            if self.eviction_policy == EvictionPolicy::Reservoir {
+                let slot = rand::thread_rng().gen_range(0..self.intervals_seen);
+                if slot < self.window_length as u64 {
+                    self.arrival_intervals[slot as usize] = arrival_interval;
+                    self.moments_cache = OnceLock::new();
+                }
+                return;
+            }
+            let evicted = match self.eviction_policy {
+                EvictionPolicy::Oldest => 0,
+                EvictionPolicy::LargestOutlier => self.largest_outlier_position(),
+                EvictionPolicy::Reservoir => unreachable!(),
+            };
+            self.arrival_intervals.remove(evicted);
+            self.n -= 1;
+        }
+        self.arrival_intervals.push(arrival_interval);
+        self.moments_cache = OnceLock::new();
+        self.n += 1;
+    }
+}
+
+impl TryFrom<(u32, &[u64])> for Statistics {
+    type Error = PhiError;
+
+    /// Statistics holding the trailing window_length intervals (in ms) of the slice, with the
+    /// last heartbeat arriving now. Fails on an empty slice, a zero window_length or intervals
+    /// whose sum does not fit in a millisecond `TimeDelta`.
+    fn try_from((window_length, intervals): (u32, &[u64])) -> Result<Self, Self::Error> {
+        if window_length == 0 {
+            return Err(PhiError::InvalidConfig("window_length must be positive".to_string()));
+        }
+        if intervals.is_empty() {
+            return Err(PhiError::EmptyWindow);
+        }
+        let total = intervals.iter().try_fold(0u64, |total, v| total.checked_add(*v));
+        if total.map_or(true, |total| total > i64::MAX as u64) {
+            return Err(PhiError::InvalidConfig("intervals overflow".to_string()));
+        }
+        let mut statistics = Statistics::new(window_length);
+        let first = intervals.len().saturating_sub(window_length as usize);
+        statistics.arrival_intervals = intervals[first..].iter().map(|v| to_interval(*v)).collect();
+        statistics.n = statistics.arrival_intervals.len() as u32 + 1;
+        Ok(statistics)
+    }
+}
+
+/// PhiCore trait for mean and variance calculation. The public queries read the moments under
+/// the same lock as the rest of their state, leaving this to the tests.
+#[async_trait]
+#[cfg_attr(not(test), allow(dead_code))]
+trait PhiCore {
+    /// Calculate mean with existing stats.
+    async fn mean_with_stats<'a>(&self, stats: Arc<RwLockReadGuard<'a, Statistics>>) -> Result<f64, Box<dyn Error>>;
+
+    /// Calculate variance and mean with existing stats.
+    async fn variance_and_mean(&self) -> Result<(f64, f64), Box<dyn Error>>;
+
+    /// Calculate variance around reference and the center it refers to with existing stats.
+    async fn variance_and_mean_around(&self, reference: VarianceReference) -> Result<(f64, f64), Box<dyn Error>>;
+}
+
+/// PhiInteraction trait for Detector
+#[async_trait]
+pub trait PhiInteraction {
+    /// Insertion of heartbeat arrival time.
+    async fn insert(&self, arrived_at: DateTime<Local>) -> Result<(), Box<dyn Error>>;
+
+    /// Trait for phi for implementing struct. For Detector phi is always within
+    /// `[0, max_phi]`: never NaN, infinite or negative.
+    async fn phi(&self, t: DateTime<Local>) -> Result<f64, Box<dyn Error>>;
+
+    /// Last arrival time of heartbeat
+    async fn last_arrived_at(&self) -> Result<DateTime<Local>, Box<dyn Error>>;
+}
+
+/// Implementation of PhiCore for Detector
+#[async_trait]
+impl PhiCore for Detector {
+    async fn mean_with_stats<'a>(&self, stats: Arc<RwLockReadGuard<'a, Statistics>>) -> Result<f64, Box<dyn Error>> {
+        Ok(stats.mean())
+    }
+
+    async fn variance_and_mean(&self) -> Result<(f64, f64), Box<dyn Error>> {
+        self.variance_and_mean_around(self.read_config()?.variance_reference).await
+    }
+
+    async fn variance_and_mean_around(&self, reference: VarianceReference) -> Result<(f64, f64), Box<dyn Error>> {
+        let stats = Arc::new(self.statistics.read().await);
+        let started = std::time::Instant::now();
+        let mu = match reference {
+            VarianceReference::SampleMean => self.mean_with_stats(Arc::clone(&stats)).await?,
+            VarianceReference::Target(target) => target,
+        };
+        let variance = stats.variance_around(mu);
+        self.record_compute(started);
+        Ok((variance, mu))
+    }
+}
+
+/// Interval in ms as stored in the window.
+#[cfg(feature = "compact")]
+fn to_interval(ms: u64) -> Interval {
+    u32::try_from(ms).unwrap_or(u32::MAX)
+}
+
+/// Interval in ms as stored in the window.
+#[cfg(not(feature = "compact"))]
+fn to_interval(ms: u64) -> Interval {
+    ms
+}
+
+/// Stored interval in ms.
+#[cfg(feature = "compact")]
+fn interval_ms(interval: Interval) -> u64 {
+    u64::from(interval)
+}
+
+/// Stored interval in ms.
+#[cfg(not(feature = "compact"))]
+fn interval_ms(interval: Interval) -> u64 {
+    interval
+}
+
+/// Mean of intervals, summed exactly and divided once.
+fn mean_of(intervals: &[Interval]) -> f64 {
+    if intervals.is_empty() {
+        return 0.;
+    }
+    let sum: u128 = intervals.iter().map(|v| u128::from(interval_ms(*v))).sum();
+    sum as f64 / intervals.len() as f64
+}
+
+/// Variance of intervals around mu, summed first and divided once.
+fn variance_of(intervals: &[Interval], mu: f64) -> f64 {
+    if intervals.is_empty() {
+        return 0.;
+    }
+    let sum: f64 = intervals.iter().map(|v| (*v as f64 - mu) * (*v as f64 - mu)).sum();
+    sum / intervals.len() as f64
+}
+
+/// Kernel bandwidth of a KDE over intervals, floored at min_std_deviation.
+fn kde_bandwidth(intervals: &[Interval], bandwidth: Bandwidth, config: &DetectorConfig) -> f64 {
+    let h = match bandwidth {
+        Bandwidth::Fixed(h) => h,
+        Bandwidth::Silverman => {
+            let sigma = variance_of(intervals, mean_of(intervals)).sqrt();
+            1.06 * sigma * (intervals.len() as f64).powf(-0.2)
+        }
+    };
+    snap_std_dev(h.max(config.min_std_deviation.num_milliseconds() as f64), config)
+}
+
+/// phi within [min_phi, max_phi] of config, counting NaN and negative phi as 0.
+fn bound_phi(phi: f64, config: &DetectorConfig) -> f64 {
+    let phi = if phi > 0. { phi.min(config.max_phi) } else { 0. };
+    phi.max(config.min_phi)
+}
+
+/// sigma, or 0 if it is within the std_dev_epsilon of config.
+fn snap_std_dev(sigma: f64, config: &DetectorConfig) -> f64 {
+    if sigma <= config.std_dev_epsilon { 0. } else { sigma }
+}
+
+/// Upper tail probability P(X > t) of the normal distribution. Computed with erfc rather than
+/// as 1 - CDF, which cancels to 0 once the CDF rounds to 1 around a z-score of 8.2. Without
+/// spread the distribution is a point mass at mu, so the tail steps from 1 to 0 past mu.
+fn normal_tail(t: f64, mu: f64, sigma: f64) -> f64 {
+    if sigma <= 0. {
+        return if t > mu { 0. } else { 1. };
+    }
+
+    let z = (t - mu) / sigma;
+    (0.5 * erfc(z / SQRT_2)).clamp(0., 1.)
+}
+
+/// Natural log of the regularized upper incomplete gamma function Q(a, x), the survival function
+/// of a gamma distribution with shape a and unit rate. Uses the series for P below a + 1 and a
+/// continued fraction for Q above, evaluated in log space so that far tails do not underflow.
+fn ln_gamma_q(a: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: u32 = 500;
+    const TINY: f64 = 1e-300;
+    if x <= 0. {
+        return 0.;
+    }
+    let ln_prefactor = -x + a * x.ln() - lgamma(a);
+    if x < a + 1. {
+        let (mut term, mut sum, mut ap) = (1. / a, 1. / a, a);
+        for _ in 0..MAX_ITERATIONS {
+            ap += 1.;
+            term *= x / ap;
+            sum += term;
+            if term.abs() < sum.abs() * f64::EPSILON {
+                break;
+            }
+        }
+        return (-(sum.ln() + ln_prefactor).exp()).ln_1p();
+    }
+    // modified Lentz evaluation of the continued fraction
+    let mut b = x + 1. - a;
+    let mut c = 1. / TINY;
+    let mut d = 1. / b;
+    let mut h = d;
+    for i in 1..MAX_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.;
+        d = an * d + b;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = b + an / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1. / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.).abs() < f64::EPSILON {
+            break;
+        }
+    }
+    ln_prefactor + h.ln()
+}
+
+/// Natural log of the regularized incomplete beta function I_x(a, b), from its continued fraction
+/// in log space so that far tails do not underflow.
+fn ln_beta_i(a: f64, b: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: u32 = 500;
+    const TINY: f64 = 1e-300;
+    if x <= 0. {
+        return f64::NEG_INFINITY;
+    }
+    if x >= 1. {
+        return 0.;
+    }
+    if x > (a + 1.) / (a + b + 2.) {
+        // the continued fraction converges quickly only below this, use I_x(a, b) = 1 - I_1-x(b, a)
+        return (-ln_beta_i(b, a, 1. - x).exp()).ln_1p();
+    }
+    let ln_prefactor = a * x.ln() + b * (-x).ln_1p() + lgamma(a + b) - lgamma(a) - lgamma(b) - a.ln();
+    // modified Lentz evaluation of the continued fraction
+    let tiny = |v: f64| if v.abs() < TINY { TINY } else { v };
+    let mut c = 1.;
+    let mut d = 1. / tiny(1. - (a + b) * x / (a + 1.));
+    let mut h = d;
+    for m in 1..MAX_ITERATIONS {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2. * m - 1.) * (a + 2. * m));
+        d = 1. / tiny(1. + even * d);
+        c = tiny(1. + even / c);
+        h *= d * c;
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2. * m) * (a + 2. * m + 1.));
+        d = 1. / tiny(1. + odd * d);
+        c = tiny(1. + odd / c);
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.).abs() < f64::EPSILON {
+            break;
+        }
+    }
+    ln_prefactor + h.ln()
+}
+
+/// Natural log of the upper tail probability P(T > z) of Student's t-distribution with nu
+/// degrees of freedom.
+fn ln_student_t_tail(z: f64, nu: f64) -> f64 {
+    let ln_half = ln_beta_i(nu / 2., 0.5, nu / (nu + z * z)) - LN_2;
+    if z > 0. {
+        ln_half
+    } else {
+        (-ln_half.exp()).ln_1p()
+    }
+}
+
+/// Phi after delta_ms since the last heartbeat for intervals with the given mean and standard
+/// deviation in ms, without a Detector. `Distribution::Gamma` is fitted by the method of moments
+/// and falls back to the normal distribution without a positive mean and spread, as do
+/// `Distribution::Kde` and `Distribution::StudentT` since they need the intervals themselves.
+/// Within `[0, DEFAULT_MAX_PHI]` like the phi of a default Detector.
+///
+/// ```rust
+/// use phi_accrual_detector::{phi_from_params, Distribution, DEFAULT_MAX_PHI};
+///
+/// let phi = phi_from_params(100., 100., 10., Distribution::Normal);
+/// assert!((phi + 0.5f64.log10()).abs() < 1e-12);
+/// assert_eq!(DEFAULT_MAX_PHI, phi_from_params(1e6, 100., 10., Distribution::Normal));
+/// ```
+pub fn phi_from_params(delta_ms: f64, mean: f64, std_dev: f64, distribution: Distribution) -> f64 {
+    unbounded_phi_from_params(delta_ms, mean, std_dev, distribution).min(DEFAULT_MAX_PHI)
+}
+
+/// Phi of `phi_from_params` before capping, left to the caller's max_phi.
+fn unbounded_phi_from_params(delta_ms: f64, mean: f64, std_dev: f64, distribution: Distribution) -> f64 {
+    if distribution == Distribution::Gamma && mean > 0. && std_dev > 0. {
+        let (shape, rate) = (mean * mean / (std_dev * std_dev), mean / (std_dev * std_dev));
+        let phi = -ln_gamma_q(shape, rate * delta_ms) / LN_10;
+        return if phi > 0. { phi } else { 0. };
+    }
+    phi_from_tail(normal_tail(delta_ms, mean, std_dev))
+}
+
+/// Phi after delta_ms, truncated to whole ms, since the last heartbeat of stats under config,
+/// through the same pipeline as `Detector::phi` but without a Detector or any locking. E.g. for
+/// the phi a node would have under another node's learned distribution. A delta_ms beyond the
+/// range of DateTime yields max_phi, or 0 if negative.
+pub fn phi_with_snapshot(delta_ms: f64, stats: &Statistics, config: &DetectorConfig) -> f64 {
+    let t = TimeDelta::try_milliseconds(delta_ms as i64).and_then(|delta| stats.last_arrived_at.checked_add_signed(delta));
+    match t {
+        Some(t) => stats.phi_at(t, config),
+        None if delta_ms > 0. => config.max_phi,
+        None => 0.,
+    }
+}
+
+/// Largest window_length whose intervals fit in bytes_per_detector next to the fixed size of
+/// Statistics, for planning registries with a memory budget. Heap-allocated histories such as
+/// the arrival log or the t-digest are not accounted for. 0 if not even the fixed size fits.
+pub fn window_length_for_memory(bytes_per_detector: usize) -> u32 {
+    let available = bytes_per_detector.saturating_sub(std::mem::size_of::<Statistics>());
+    u32::try_from(available / std::mem::size_of::<Interval>()).unwrap_or(u32::MAX)
+}
+
+/// Phi for an upper tail probability, normalizing the -0.0 of -log10(1) and NaN to 0.0
+fn phi_from_tail(tail: f64) -> f64 {
+    let phi = -log10(tail);
+    if phi > 0. {
+        phi
+    } else {
+        0.
+    }
+}
+
+impl Detector {
+    /// Phi at t evaluated against a single configuration snapshot.
+    async fn phi_with_config(&self, t: DateTime<Local>, config: &DetectorConfig) -> Result<f64, Box<dyn Error>> {
+        Ok(self.phi_of(&*self.statistics.read().await, t, config))
+    }
+
+    /// Phi at t for stats, which the caller reads under the lock it also takes its other values
+    /// under, so that they all come from the same state.
+    fn phi_of(&self, stats: &Statistics, t: DateTime<Local>, config: &DetectorConfig) -> f64 {
+        let started = std::time::Instant::now();
+        let phi = stats.phi_at(t, config);
+        self.record_compute(started);
+        self.last_phi.store(phi, t, self.generation.load(Ordering::Relaxed));
+        phi
+    }
+
+    /// Phi at t for stats like `phi_of`, reusing the last computed phi if it was computed for
+    /// t from the current state. Only for config read from the Detector itself.
+    fn cached_phi_of(&self, stats: &Statistics, t: DateTime<Local>, config: &DetectorConfig) -> f64 {
+        match self.last_phi.load_for(t, self.generation.load(Ordering::Relaxed)) {
+            Some(phi) => phi,
+            None => self.phi_of(stats, t, config),
+        }
+    }
+
+    /// Wake the waiters on changed and invalidate the cached phi. Called with the statistics
+    /// write lock held, so that readers see the new generation along with the new state.
+    fn notify_changed(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.changed.notify_waiters();
+    }
+
+    /// Record the time since started as the duration of the last computation.
+    #[cfg(feature = "timing")]
+    fn record_compute(&self, started: std::time::Instant) {
+        let nanos = u64::try_from(started.elapsed().as_nanos()).unwrap_or(u64::MAX - 1);
+        self.compute_nanos.store(nanos, Ordering::Relaxed);
+    }
+
+    #[cfg(not(feature = "timing"))]
+    fn record_compute(&self, _started: std::time::Instant) {}
+
+    /// Time the last phi or variance and mean computation took, excluding waiting for locks.
+    /// None before the first one. Sheds light on the cost of large windows.
+    #[cfg(feature = "timing")]
+    pub fn last_compute_duration(&self) -> Option<std::time::Duration> {
+        match self.compute_nanos.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            nanos => Some(std::time::Duration::from_nanos(nanos)),
+        }
+    }
+}
+
+/// The detector with the lower phi at t. Ties resolve to `a`, so argument order breaks ties.
+pub async fn healthier_of<'a>(a: &'a Detector, b: &'a Detector, t: DateTime<Local>) -> Result<&'a Detector, Box<dyn Error>> {
+    let phi_a = a.phi(t).await?;
+    let phi_b = b.phi(t).await?;
+    Ok(if phi_b < phi_a { b } else { a })
+}
+
+/// Implementation of PhiInteraction for Detector
+#[async_trait]
+impl PhiInteraction for Detector {
+    async fn insert(&self, arrived_at: DateTime<Local>) -> Result<(), Box<dyn Error>> {
+        let config = self.read_config()?;
+        let mut stats = self.statistics.write().await;
+        stats.record_arrival_phi(arrived_at, &config);
+        stats.insert(arrived_at);
+        self.notify_changed();
+        Ok(())
+    }
+
+    async fn phi(&self, t: DateTime<Local>) -> Result<f64, Box<dyn Error>> {
+        let config = self.read_config()?;
+        self.phi_with_config(t, &config).await
+    }
+
+    async fn last_arrived_at(&self) -> Result<DateTime<Local>, Box<dyn Error>> {
+        Ok(self.statistics.read().await.last_arrived_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::{Add, Sub};
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+    use chrono::{Duration, Local, TimeDelta};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use tokio::sync::RwLock;
+    use crate::{healthier_of, interval_ms, ln_gamma_q, ln_student_t_tail, mean_of, phi_with_snapshot, window_length_for_memory, Interval, ArrivalLogEntry, Bandwidth, Detector, DetectorBuilder, DetectorConfig, DetectorSummary, Distribution, DistributionFamily, DecayKernel, DistributionParams, Evaluation, Event, EvictionPolicy, GapHandling, IntervalsSummary, NodeStatus, OutageHandling, PhiCore, PhiError, PhiInteraction, PoisonPolicy, ProbationPolicy, Rejection, Severity, SeverityBands, StalenessReport, Statistics, StatisticsDiff, TryError, VarianceReference};
+
+    #[tokio::test]
+    async fn test_variant_mean_and_variance_combo_calculation() {
+        let mut stats = Statistics::new(10);
+        let mut i = 0;
+        let mut curr_time = Local::now();
+        stats.insert(curr_time);
+        let expect_vals = [1630, 4421, 1514, 216, 231, 931, 4182, 102, 104, 241, 5132];
+        while i < expect_vals.len() {
+            curr_time = curr_time.add(Duration::milliseconds(expect_vals[i]));
+            let arrived_at = curr_time;
+            stats.insert(arrived_at);
+            i += 1;
+        }
+        let detector = Detector {
+            statistics: RwLock::new(stats),
+            ..Detector::new(10)
+        };
+        let (mut variance, mut mean) = detector.variance_and_mean().await.unwrap();
+        mean = (mean * 100.0).round() * 0.01;
+        variance = (variance * 100.0).round() * 0.01;
+        assert_eq!(1707.4, mean);
+        assert_eq!(3755791.64, variance);
+
+        let mut suspicion_level: Vec<f64> = vec![];
+        for _ in 1..10 {
+            curr_time = curr_time.add(Duration::milliseconds(250));
+            suspicion_level.push(detector.phi(curr_time).await.unwrap())
+        }
+        println!("suspicion -> {:?}", suspicion_level);
+        for i in 1..suspicion_level.len() {
+            assert!(suspicion_level[i] > suspicion_level[i - 1]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_after_ms_matches_timestamp_inserts() {
+        let detector = Detector::new(10);
+        let mut curr_time = Local::now();
+        detector.insert(curr_time).await.unwrap();
+        for interval in [1630, 4421, 1514, 216, 231, 931, 4182, 102, 104, 241, 5132] {
+            curr_time = detector.insert_after_ms(interval).await.unwrap();
+        }
+        let (mut variance, mut mean) = detector.variance_and_mean().await.unwrap();
+        mean = (mean * 100.0).round() * 0.01;
+        variance = (variance * 100.0).round() * 0.01;
+        assert_eq!(1707.4, mean);
+        assert_eq!(3755791.64, variance);
+        assert_eq!(curr_time, detector.last_arrived_at().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_warm_start_from_is_independent_copy() {
+        let source = Detector::new(10);
+        source.insert(Local::now()).await.unwrap();
+        for interval in [100, 120, 80, 110] {
+            source.insert_after_ms(interval).await.unwrap();
+        }
+        let replica = Detector::new(3);
+        replica.warm_start_from(&source).await.unwrap();
+        let t = source.last_arrived_at().await.unwrap().add(Duration::milliseconds(150));
+        assert_eq!(vec![120, 80, 110], replica.statistics.read().await.arrival_intervals[..]);
+
+        let full_replica = Detector::new(10);
+        full_replica.warm_start_from(&source).await.unwrap();
+        assert_eq!(source.phi(t).await.unwrap(), full_replica.phi(t).await.unwrap());
+
+        full_replica.insert_after_ms(500).await.unwrap();
+        source.insert_after_ms(90).await.unwrap();
+        assert_eq!(vec![100, 120, 80, 110, 90], source.statistics.read().await.arrival_intervals[..]);
+        assert_eq!(vec![100, 120, 80, 110, 500], full_replica.statistics.read().await.arrival_intervals[..]);
+    }
+
+    #[tokio::test]
+    async fn test_largest_outlier_eviction_drops_biggest_interval() {
+        let oldest = DetectorBuilder::new().window_length(4).build();
+        let outlier = DetectorBuilder::new()
+            .window_length(4)
+            .eviction_policy(EvictionPolicy::LargestOutlier)
+            .build();
+        let start = Local::now();
+        for detector in [&oldest, &outlier] {
+            detector.insert(start).await.unwrap();
+            for interval in [100, 110, 900, 90, 105] {
+                detector.insert_after_ms(interval).await.unwrap();
+            }
+        }
+        assert_eq!(vec![110, 900, 90, 105], oldest.statistics.read().await.arrival_intervals[..]);
+        assert_eq!(vec![100, 110, 90, 105], outlier.statistics.read().await.arrival_intervals[..]);
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_matches_sequential_inserts() {
+        let start = Local::now();
+        let mut arrivals = vec![start];
+        for i in 1..10_000 {
+            let interval = 100 + (i * 7919) % 53;
+            arrivals.push(arrivals[i as usize - 1].add(Duration::milliseconds(interval)));
+        }
+        let mut sequential = Statistics::new(1000);
+        for arrived_at in &arrivals {
+            sequential.insert(*arrived_at);
+        }
+        let rebuilt = Statistics::rebuild(&arrivals, 1000).unwrap();
+        assert_eq!(sequential.arrival_intervals, rebuilt.arrival_intervals);
+        assert_eq!(sequential.last_arrived_at, rebuilt.last_arrived_at);
+        assert_eq!(sequential.n, rebuilt.n);
+
+        let config = DetectorConfig { window_length: 1000, ..Default::default() };
+        let detector = Detector::from_history(config, &arrivals).unwrap();
+        assert_eq!(sequential.arrival_intervals, detector.statistics.read().await.arrival_intervals);
+
+        arrivals.swap(10, 11);
+        assert_eq!(PhiError::UnorderedArrivals { index: 11 }, Statistics::rebuild(&arrivals, 1000).unwrap_err());
+        assert_eq!(0, Statistics::rebuild(&[], 10).unwrap().n);
+    }
+
+    #[tokio::test]
+    async fn test_from_history_matches_sequential_inserts() {
+        let start = Local::now();
+        let arrivals: Vec<_> = [0, 100, 105, 200, 300, 302, 400, 3000, 3100]
+            .into_iter()
+            .map(|ms| start.add(Duration::milliseconds(ms)))
+            .collect();
+        let configs = [
+            DetectorConfig { window_length: 5, min_heartbeat_spacing_ms: Some(10), ..Default::default() },
+            DetectorConfig { window_length: 5, arrival_log: Some(4), ..Default::default() },
+            DetectorConfig { window_length: 5, outage_handling: OutageHandling::Exclude(10.), ..Default::default() },
+            DetectorConfig { window_length: 5, eviction_policy: EvictionPolicy::LargestOutlier, ..Default::default() },
+        ];
+        for config in configs {
+            let sequential = Detector::from_config(config.clone());
+            for arrived_at in &arrivals {
+                sequential.insert(*arrived_at).await.unwrap();
+            }
+            let rebuilt = Detector::from_history(config, &arrivals).unwrap();
+            assert_eq!(sequential.arrival_log().await, rebuilt.arrival_log().await);
+            let (expected, actual) = (sequential.statistics.read().await, rebuilt.statistics.read().await);
+            assert_eq!(expected.arrival_intervals, actual.arrival_intervals);
+            assert_eq!(expected.n, actual.n);
+            assert_eq!(expected.rate_limited, actual.rate_limited);
+            assert_eq!(expected.outages, actual.outages);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_percentile_into_matches_allocating_percentile() {
+        let detector = Detector::new(20);
+        detector.insert(Local::now()).await.unwrap();
+        for interval in [1630, 4421, 1514, 216, 231, 931, 4182, 102, 104, 241, 5132] {
+            detector.insert_after_ms(interval).await.unwrap();
+        }
+        let mut scratch = Vec::with_capacity(20);
+        for p in [0., 10., 50., 90., 95., 100.] {
+            assert_eq!(detector.percentile(p).await.unwrap(), detector.percentile_into(p, &mut scratch).await.unwrap());
+        }
+        assert_eq!(102, detector.percentile(0.).await.unwrap());
+        assert_eq!(931, detector.percentile(50.).await.unwrap());
+        assert_eq!(5132, detector.percentile(100.).await.unwrap());
+        assert!(detector.percentile(101.).await.is_err());
+        assert!(Detector::new(20).percentile(50.).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_apply_config_is_atomic_for_concurrent_phi() {
+        let strict = DetectorConfig { window_length: 10, ..Default::default() };
+        let lenient = DetectorConfig {
+            window_length: 10,
+            acceptable_pause: TimeDelta::milliseconds(300),
+            min_std_deviation: TimeDelta::milliseconds(200),
+            ..Default::default()
+        };
+        let detector = Arc::new(Detector::from_config(strict.clone()));
+        let start = Local::now();
+        detector.insert(start).await.unwrap();
+        for interval in [100, 120, 80, 110, 90] {
+            detector.insert_after_ms(interval).await.unwrap();
+        }
+        let t = detector.last_arrived_at().await.unwrap().add(Duration::milliseconds(400));
+        let strict_phi = detector.phi(t).await.unwrap();
+        detector.apply_config(lenient.clone()).await.unwrap();
+        let lenient_phi = detector.phi(t).await.unwrap();
+        assert_ne!(strict_phi, lenient_phi);
+
+        let flipper = {
+            let detector = Arc::clone(&detector);
+            tokio::spawn(async move {
+                for i in 0..500 {
+                    let config = if i % 2 == 0 { strict.clone() } else { lenient.clone() };
+                    detector.apply_config(config).await.unwrap();
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+        let mut readers = vec![];
+        for _ in 0..4 {
+            let detector = Arc::clone(&detector);
+            readers.push(tokio::spawn(async move {
+                for _ in 0..500 {
+                    let phi = detector.phi(t).await.unwrap();
+                    assert!(phi == strict_phi || phi == lenient_phi, "phi {} mixes configurations", phi);
+                }
+            }));
+        }
+        flipper.await.unwrap();
+        for reader in readers {
+            reader.await.unwrap();
+        }
+        let invalid = DetectorConfig { phi_smoothing: 0., ..Default::default() };
+        assert!(detector.apply_config(invalid).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recent_health_bits_flag_late_intervals() {
+        let detector = Detector::new(10);
+        let err = detector.recent_health_bits(64).await.unwrap_err();
+        assert_eq!(Some(&PhiError::InsufficientSamples { available: 0, required: 1 }), err.downcast_ref::<PhiError>());
+        detector.insert(Local::now()).await.unwrap();
+        detector.insert_after_ms(400).await.unwrap();
+        assert_eq!(1, detector.recent_health_bits(64).await.unwrap());
+        for interval in [100, 100, 100, 100, 400, 100, 100] {
+            detector.insert_after_ms(interval).await.unwrap();
+        }
+        assert_eq!(0b1111011, detector.recent_health_bits(7).await.unwrap());
+        assert_eq!(0b1011, detector.recent_health_bits(4).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_min_accept_interval_rate_limits_floods() {
+        let detector = DetectorBuilder::new()
+            .window_length(20)
+            .min_accept_interval(TimeDelta::milliseconds(50))
+            .min_std_deviation(TimeDelta::milliseconds(10))
+            .build();
+        let start = Local::now();
+        for i in 0..10 {
+            detector.insert(start.add(Duration::milliseconds(i * 100))).await.unwrap();
+        }
+        for i in 901..950 {
+            detector.insert(start.add(Duration::milliseconds(i))).await.unwrap();
+        }
+        assert_eq!(49, detector.rate_limited().await);
+        assert_eq!(vec![100; 9], detector.statistics.read().await.arrival_intervals[..]);
+
+        let last_arrived_at = detector.last_arrived_at().await.unwrap();
+        assert_eq!(start.add(Duration::milliseconds(949)), last_arrived_at);
+        assert!(detector.phi(last_arrived_at.add(Duration::milliseconds(50))).await.unwrap() < 1.);
+        assert!(detector.phi(last_arrived_at.add(Duration::milliseconds(1000))).await.unwrap() > 8.);
+    }
+
+    #[tokio::test]
+    async fn test_out_of_order_heartbeat_is_not_rate_limited() {
+        let detector = DetectorBuilder::new().with_arrival_log(2).build();
+        let start = Local::now();
+        for ms in [0, 100, 200, 300, 250] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        assert_eq!(0, detector.rate_limited().await);
+        assert_eq!(start.add(Duration::milliseconds(300)), detector.last_arrived_at().await.unwrap());
+        assert_eq!(vec![100; 3], detector.statistics.read().await.arrival_intervals[..]);
+        let rejection = detector.arrival_log().await.last().unwrap().rejection;
+        assert_eq!(Some(Rejection::OutOfOrder), rejection);
+    }
+
+    #[tokio::test]
+    async fn test_target_variance_reference_flags_consistently_slow_node() {
+        let sample_mean = DetectorBuilder::new().window_length(10).build();
+        let target = DetectorBuilder::new()
+            .window_length(10)
+            .variance_reference(VarianceReference::Target(100.))
+            .build();
+        let start = Local::now();
+        for detector in [&sample_mean, &target] {
+            detector.insert(start).await.unwrap();
+            for interval in [150, 155, 145, 152, 148] {
+                detector.insert_after_ms(interval).await.unwrap();
+            }
+        }
+        let (_, mu) = target.variance_and_mean().await.unwrap();
+        assert_eq!(100., mu);
+        let t = sample_mean.last_arrived_at().await.unwrap().add(Duration::milliseconds(150));
+        assert!(target.phi(t).await.unwrap() > sample_mean.phi(t).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_describe_mentions_phi_and_last_seen() {
+        let detector = Detector::new(10);
+        detector.insert(Local::now()).await.unwrap();
+        for interval in [1630, 4421, 1514, 216, 231, 931, 4182, 102, 104, 241, 5132] {
+            detector.insert_after_ms(interval).await.unwrap();
+        }
+        let t = detector.last_arrived_at().await.unwrap().add(Duration::milliseconds(1200));
+        let phi = detector.phi(t).await.unwrap();
+        let description = detector.describe(t, 8.).await.unwrap();
+        assert_eq!(format!("Node last seen 1.2s ago; typical interval 1.7s ± 1.9s; phi {:.1} (healthy).", phi), description);
+        assert!(detector.describe(t, 0.).await.unwrap().ends_with("(suspected)."));
+        detector.apply_config(DetectorConfig { min_std_deviation: TimeDelta::seconds(3), ..detector.config().unwrap() }).await.unwrap();
+        assert!(detector.describe(t, 8.).await.unwrap().contains("1.7s ± 3.0s"));
+    }
+
+    #[tokio::test]
+    async fn test_try_variants_return_would_block_under_write_lock() {
+        let detector = Arc::new(Detector::new(10));
+        let start = Local::now();
+        detector.try_insert(start).unwrap();
+        detector.try_insert(start.add(Duration::milliseconds(100))).unwrap();
+        detector.try_insert(start.add(Duration::milliseconds(220))).unwrap();
+        let t = start.add(Duration::milliseconds(400));
+        assert_eq!(detector.phi(t).await.unwrap(), detector.try_phi(t).unwrap());
+
+        let (locked_tx, locked_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let holder = {
+            let detector = Arc::clone(&detector);
+            tokio::spawn(async move {
+                let _stats = detector.statistics.write().await;
+                locked_tx.send(()).unwrap();
+                release_rx.await.unwrap();
+            })
+        };
+        locked_rx.await.unwrap();
+        assert_eq!(Err(TryError::WouldBlock), detector.try_insert(t));
+        assert_eq!(Err(TryError::WouldBlock), detector.try_phi(t));
+        release_tx.send(()).unwrap();
+        holder.await.unwrap();
+        assert!(detector.try_phi(t).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rebase_clock_keeps_window() {
+        let detector = Detector::new(10);
+        let start = Local::now().sub(Duration::seconds(60));
+        detector.insert(start).await.unwrap();
+        for interval in [100, 120, 80] {
+            detector.insert_after_ms(interval).await.unwrap();
+        }
+        let before = Local::now();
+        detector.rebase_clock().await;
+        let stats = detector.statistics.read().await;
+        assert_eq!(vec![100, 120, 80], stats.arrival_intervals[..]);
+        assert_eq!(4, stats.n);
+        assert!(stats.last_arrived_at >= before);
+        drop(stats);
+
+        detector.insert_after_ms(90).await.unwrap();
+        assert_eq!(vec![100, 120, 80, 90], detector.statistics.read().await.arrival_intervals[..]);
+    }
+
+    #[tokio::test]
+    async fn test_known_period_ignores_burst_of_short_intervals() {
+        let learned = DetectorBuilder::new()
+            .window_length(10)
+            .min_std_deviation(TimeDelta::milliseconds(20))
+            .build();
+        let known = DetectorBuilder::new()
+            .window_length(10)
+            .min_std_deviation(TimeDelta::milliseconds(20))
+            .known_period(TimeDelta::seconds(1))
+            .build();
+        let start = Local::now();
+        for detector in [&learned, &known] {
+            detector.insert(start).await.unwrap();
+            detector.insert_after_ms(1010).await.unwrap();
+        }
+        assert_eq!(1000., known.variance_and_mean().await.unwrap().1);
+
+        for detector in [&learned, &known] {
+            for interval in [990, 1000, 1005, 900, 905, 895] {
+                detector.insert_after_ms(interval).await.unwrap();
+            }
+        }
+        let t = known.last_arrived_at().await.unwrap().add(Duration::milliseconds(1000));
+        assert!(known.phi(t).await.unwrap() < 1.);
+        assert!(learned.phi(t).await.unwrap() > known.phi(t).await.unwrap());
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn test_inline_and_spilled_windows_agree() {
+        let start = Local::now();
+        let mut inline = Statistics::new(20);
+        let mut spilled = Statistics::new(20);
+        spilled.arrival_intervals.grow(64);
+        let mut arrived_at = start;
+        for i in 0..40 {
+            arrived_at = arrived_at.add(Duration::milliseconds(100 + i % 7 * 10));
+            inline.insert(arrived_at);
+            spilled.insert(arrived_at);
+        }
+        assert!(!inline.arrival_intervals.spilled());
+        assert!(spilled.arrival_intervals.spilled());
+        assert_eq!(inline.arrival_intervals, spilled.arrival_intervals);
+        assert_eq!(inline.mean(), spilled.mean());
+        assert_eq!(inline.variance_around(inline.mean()), spilled.variance_around(spilled.mean()));
+        let config = DetectorConfig::default();
+        let t = arrived_at.add(Duration::milliseconds(150));
+        assert_eq!(inline.phi_at(t, &config), spilled.phi_at(t, &config));
+    }
+
+    #[tokio::test]
+    async fn test_phi_stays_within_zero_and_max_phi() {
+        let mut rng = StdRng::seed_from_u64(164);
+        let start = Local::now();
+        for case in 0..300 {
+            let mut builder = DetectorBuilder::new()
+                .window_length(rng.gen_range(1..20))
+                .acceptable_pause(TimeDelta::milliseconds(rng.gen_range(0..500)))
+                .min_std_deviation(TimeDelta::milliseconds(if case % 2 == 0 { 0 } else { rng.gen_range(0..50) }));
+            if case % 3 == 0 {
+                builder = builder.known_period(TimeDelta::milliseconds(rng.gen_range(0..2000)));
+            }
+            let detector = builder.build();
+            let max_phi = detector.config().unwrap().max_phi;
+
+            // constant, zero and arbitrary intervals
+            let constant = rng.gen_range(0..1000);
+            let mut arrived_at = start;
+            for _ in 0..rng.gen_range(0..30) {
+                let interval = match case % 4 {
+                    0 => constant,
+                    1 => 0,
+                    _ => rng.gen_range(0..5000),
+                };
+                arrived_at = arrived_at.add(Duration::milliseconds(interval));
+                detector.insert(arrived_at).await.unwrap();
+            }
+
+            let offsets: Vec<i64> = (0..10).map(|_| rng.gen_range(-5000..1_000_000)).collect();
+            let ts: Vec<_> = offsets.iter().map(|ms| arrived_at.add(Duration::milliseconds(*ms))).collect();
+            let mut phis = detector.phi_batch(&ts).await.unwrap();
+            for (ms, t) in offsets.iter().zip(&ts) {
+                phis.push(detector.phi(*t).await.unwrap());
+                phis.push(detector.phi_for_elapsed(TimeDelta::milliseconds(*ms)).await.unwrap());
+            }
+            for phi in phis {
+                assert!((0. ..=max_phi).contains(&phi), "case {case}: phi {phi}");
+                assert!(phi.is_sign_positive(), "case {case}: phi {phi}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_phi_from_seeded_moments() {
+        let start = Local::now();
+        let stats = Statistics::from_moments(10, 1000., 10000., start).unwrap();
+        let detector = Detector::from_statistics(DetectorConfig::default(), stats);
+        assert_eq!((10000., 1000.), detector.variance_and_mean().await.unwrap());
+        let phi = detector.phi(start.add(Duration::milliseconds(1200))).await.unwrap();
+        assert!((phi - 1.643).abs() < 1e-3, "phi {phi}");
+
+        detector.insert(start.add(Duration::milliseconds(500))).await.unwrap();
+        assert_eq!((0., 500.), detector.variance_and_mean().await.unwrap());
+        assert!(Statistics::from_moments(10, 1000., f64::NAN, start).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_startup_grace_suppresses_phi() {
+        let start = Local::now();
+        let stats = Statistics::from_moments(10, 100., 100., start).unwrap();
+        let config = DetectorBuilder::new().startup_grace(TimeDelta::seconds(2)).config().clone();
+        let detector = Detector::from_statistics(config, stats);
+        assert_eq!(0., detector.phi(start.add(Duration::seconds(1))).await.unwrap());
+        assert!(detector.phi(start.add(Duration::seconds(3))).await.unwrap() > 8.);
+
+        detector.rebase_clock().await;
+        let resumed = detector.last_arrived_at().await.unwrap();
+        assert_eq!(0., detector.phi(resumed.add(Duration::seconds(1))).await.unwrap());
+        assert!(detector.phi(resumed.add(Duration::seconds(3))).await.unwrap() > 8.);
+    }
+
+    #[tokio::test]
+    async fn test_phi_recent_follows_late_cadence() {
+        let detector = DetectorBuilder::new().min_samples(5).build();
+        detector.insert(Local::now()).await.unwrap();
+        for i in 0..20 {
+            detector.insert_after_ms(1000 + i % 3 * 20).await.unwrap();
+        }
+        for i in 0..20 {
+            detector.insert_after_ms(100 + i % 3 * 10).await.unwrap();
+        }
+        let t = detector.last_arrived_at().await.unwrap().add(Duration::milliseconds(400));
+        let full = detector.phi(t).await.unwrap();
+        let recent = detector.phi_recent(t, 20).await.unwrap();
+        assert!(full < 1., "full {full}");
+        assert!(recent > 8., "recent {recent}");
+
+        let late = DetectorBuilder::new().min_samples(5).build();
+        late.insert(Local::now()).await.unwrap();
+        for i in 0..20 {
+            late.insert_after_ms(100 + i % 3 * 10).await.unwrap();
+        }
+        let t = detector.last_arrived_at().await.unwrap().add(Duration::milliseconds(150));
+        let late_phi = late.phi_for_elapsed(TimeDelta::milliseconds(150)).await.unwrap();
+        assert_eq!(late_phi, detector.phi_recent(t, 20).await.unwrap());
+
+        let err = detector.phi_recent(t, 3).await.unwrap_err();
+        assert_eq!(Some(&PhiError::InsufficientSamples { available: 3, required: 5 }), err.downcast_ref::<PhiError>());
+    }
+
+    #[tokio::test]
+    async fn test_intervals_summary() {
+        let detector = Detector::new(10);
+        assert!(detector.intervals_summary().await.is_err());
+        detector.insert(Local::now()).await.unwrap();
+        for ms in [100, 120, 80, 110] {
+            detector.insert_after_ms(ms).await.unwrap();
+        }
+        let summary = detector.intervals_summary().await.unwrap();
+        assert_eq!(IntervalsSummary { count: 4, sum_ms: 410, min_ms: 80, max_ms: 120 }, summary);
+    }
+
+    #[cfg(not(feature = "compact"))]
+    #[tokio::test]
+    async fn test_intervals_summary_sum_saturates() {
+        let detector = Detector::new(10);
+        {
+            let mut stats = detector.statistics.write().await;
+            stats.insert(Local::now());
+            for _ in 0..3 {
+                stats.push_interval(u64::MAX / 2);
+            }
+        }
+        let summary = detector.intervals_summary().await.unwrap();
+        assert_eq!(IntervalsSummary { count: 3, sum_ms: u64::MAX, min_ms: u64::MAX / 2, max_ms: u64::MAX / 2 }, summary);
+    }
+
+    fn kde_detector(bandwidth: Bandwidth) -> Detector {
+        DetectorBuilder::new().distribution(Distribution::Kde { bandwidth }).build()
+    }
+
+    #[tokio::test]
+    async fn test_kde_phi_is_monotonic() {
+        let detector = kde_detector(Bandwidth::Silverman);
+        detector.insert(Local::now()).await.unwrap();
+        for ms in [100, 120, 80, 300, 90, 110, 500, 100] {
+            detector.insert_after_ms(ms).await.unwrap();
+        }
+        let mut previous = 0.;
+        for elapsed in (0..2000).step_by(10) {
+            let phi = detector.phi_for_elapsed(TimeDelta::milliseconds(elapsed)).await.unwrap();
+            assert!(phi >= previous, "phi {phi} after {previous} at {elapsed}ms");
+            previous = phi;
+        }
+        assert!(previous > 8.);
+    }
+
+    #[tokio::test]
+    async fn test_kde_agrees_with_normal_on_normal_data() {
+        let mut rng = StdRng::seed_from_u64(167);
+        let normal = Detector::new(1000);
+        let kde = kde_detector(Bandwidth::Silverman);
+        let start = Local::now();
+        normal.insert(start).await.unwrap();
+        kde.insert(start).await.unwrap();
+        let mut arrived_at = start;
+        for _ in 0..1000 {
+            // Box-Muller sample of N(1000, 50)
+            let (u1, u2): (f64, f64) = (rng.gen::<f64>().max(f64::MIN_POSITIVE), rng.gen());
+            let interval = 1000. + 50. * (-2. * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+            arrived_at = arrived_at.add(Duration::milliseconds(interval as i64));
+            normal.insert(arrived_at).await.unwrap();
+            kde.insert(arrived_at).await.unwrap();
+        }
+        for elapsed in [900, 1000, 1050, 1100, 1150] {
+            let elapsed = TimeDelta::milliseconds(elapsed);
+            let normal_phi = normal.phi_for_elapsed(elapsed).await.unwrap();
+            let kde_phi = kde.phi_for_elapsed(elapsed).await.unwrap();
+            // the kernels widen the tail slightly
+            assert!((normal_phi - kde_phi).abs() < 0.05 + 0.15 * normal_phi, "normal {normal_phi}, kde {kde_phi}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kde_tracks_bimodal_intervals() {
+        let normal = DetectorBuilder::new().build();
+        let kde = kde_detector(Bandwidth::Fixed(20.));
+        let start = Local::now();
+        normal.insert(start).await.unwrap();
+        kde.insert(start).await.unwrap();
+        for i in 0..100 {
+            let interval = if i % 2 == 0 { 100 + i % 3 * 10 } else { 1000 + i % 3 * 10 };
+            normal.insert_after_ms(interval).await.unwrap();
+            kde.insert_after_ms(interval).await.unwrap();
+        }
+        // past the slow mode the node is overdue, which the normal model blurs away
+        let overdue = TimeDelta::milliseconds(1300);
+        assert!(normal.phi_for_elapsed(overdue).await.unwrap() < 2.);
+        assert!(kde.phi_for_elapsed(overdue).await.unwrap() > 8.);
+        // between the modes half of the heartbeats are still expected
+        let between = TimeDelta::milliseconds(500);
+        assert!((kde.phi_for_elapsed(between).await.unwrap() - 0.5f64.log10().abs()).abs() < 0.01);
+    }
+
+    #[tokio::test]
+    async fn test_flap_count_of_oscillating_node() {
+        let builder = || DetectorBuilder::new()
+            .window_length(10)
+            .eviction_policy(EvictionPolicy::LargestOutlier)
+            .min_std_deviation(TimeDelta::milliseconds(20));
+        let (detector, untracked) = (builder().with_flap_history(64).build(), builder().build());
+        let mut at = Local::now().sub(Duration::seconds(30));
+        let mut intervals = vec![];
+        for _ in 0..3 {
+            intervals.extend((0..10).map(|i| 100 + i % 3 * 10));
+            intervals.push(1000);
+        }
+        intervals.extend((0..10).map(|i| 100 + i % 3 * 10));
+        for interval in std::iter::once(0).chain(intervals) {
+            at = at.add(Duration::milliseconds(interval));
+            detector.insert(at).await.unwrap();
+            untracked.insert(at).await.unwrap();
+        }
+        let minute = Duration::minutes(1);
+        assert_eq!(6, detector.flap_count_at(at, minute, 8.).await.unwrap());
+        assert_eq!(0, detector.flap_count_at(at, minute, 1000.).await.unwrap());
+        assert_eq!(0, detector.flap_count_at(at, Duration::seconds(1), 8.).await.unwrap());
+        let error = untracked.flap_count_at(at, minute, 8.).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<PhiError>(), Some(PhiError::InvalidConfig(_))));
+        // suspected again, with the crossing above threshold not yet followed by a heartbeat
+        let later = at.add(Duration::seconds(1));
+        assert_eq!(7, detector.flap_count_at(later, minute, 8.).await.unwrap());
+        assert_eq!(1, detector.flap_count_at(later, Duration::seconds(1), 8.).await.unwrap());
+        assert_eq!(7, detector.flap_count(minute, 8.).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_moments_computed_once_per_heartbeat() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let computed = Arc::clone(&detector.statistics.read().await.moments_computed);
+        detector.insert(Local::now()).await.unwrap();
+        for (i, ms) in [100, 120, 80].into_iter().enumerate() {
+            let arrived_at = detector.insert_after_ms(ms).await.unwrap();
+            let before = computed.load(Ordering::Relaxed);
+            let mut previous = 0.;
+            for elapsed in 0..50 {
+                let phi = detector.phi(arrived_at.add(Duration::milliseconds(elapsed * 5))).await.unwrap();
+                assert!(phi >= previous);
+                previous = phi;
+            }
+            assert_eq!(before + 1, computed.load(Ordering::Relaxed), "heartbeat {i}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_staleness_report() {
+        let detector = DetectorBuilder::new().min_samples(3).min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        assert_eq!(None, detector.time_since_last_heartbeat(start).await.unwrap());
+        let idle = detector.staleness(start).await.unwrap();
+        assert_eq!(StalenessReport { elapsed: None, phi: 0., samples: 0, warmed_up: false, last_arrived_at: None }, idle);
+
+        let mut clock = start;
+        for ms in [0, 100, 110, 90] {
+            clock = clock.add(Duration::milliseconds(ms));
+            detector.insert(clock).await.unwrap();
+            let report = detector.staleness(clock.add(Duration::milliseconds(50))).await.unwrap();
+            assert_eq!(Some(TimeDelta::milliseconds(50)), report.elapsed);
+            assert_eq!(Some(clock), report.last_arrived_at);
+        }
+        let now = clock.add(Duration::milliseconds(400));
+        let report = detector.staleness(now).await.unwrap();
+        assert_eq!(Some(TimeDelta::milliseconds(400)), detector.time_since_last_heartbeat(now).await.unwrap());
+        assert_eq!(Some(TimeDelta::milliseconds(400)), report.elapsed);
+        assert_eq!(detector.phi(now).await.unwrap(), report.phi);
+        assert_eq!(3, report.samples);
+        assert!(report.warmed_up);
+    }
+
+    #[tokio::test]
+    async fn test_phi_timeline() {
+        let detector = Detector::new(10);
+        detector.insert(Local::now()).await.unwrap();
+        for ms in [100, 120, 80, 110] {
+            detector.insert_after_ms(ms).await.unwrap();
+        }
+        let timeline = detector.phi_timeline(-300, 300, 50).await.unwrap();
+        assert_eq!(13, timeline.len());
+        assert_eq!((-300, 0.), timeline[0]);
+        assert_eq!((300, detector.phi_for_elapsed(TimeDelta::milliseconds(300)).await.unwrap()), timeline[12]);
+        assert!(timeline.iter().filter(|(offset, _)| *offset < 0).all(|(_, phi)| *phi == 0.));
+        let future: Vec<f64> = timeline.iter().filter(|(offset, _)| *offset >= 50).map(|(_, phi)| *phi).collect();
+        assert!(future.windows(2).all(|pair| pair[0] <= pair[1]), "{future:?}");
+        assert!(future[0] < future[future.len() - 1]);
+    }
+
+    #[tokio::test]
+    async fn test_min_heartbeat_spacing_debounces_bursts() {
+        let detector = DetectorBuilder::new().min_heartbeat_spacing_ms(50).build();
+        let start = Local::now();
+        for at in [0, 10, 20, 30, 100, 105, 149, 150, 300] {
+            detector.insert(start.add(Duration::milliseconds(at))).await.unwrap();
+        }
+        assert_eq!(vec![100, 50, 150], detector.statistics.read().await.arrival_intervals[..]);
+        assert_eq!(start.add(Duration::milliseconds(300)), detector.last_arrived_at().await.unwrap());
+        assert_eq!(0, detector.rate_limited().await);
+    }
+
+    #[test]
+    fn test_gamma_survival_function() {
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-10 * b;
+        // shape 1 is the exponential distribution
+        for x in [0.1, 0.5, 1., 2., 10., 100.] {
+            assert!(close(ln_gamma_q(1., x).exp(), (-x).exp()), "x {x}");
+        }
+        // Q(2, x) = e^-x (1 + x)
+        for x in [0.5, 3., 20.] {
+            assert!(close(ln_gamma_q(2., x).exp(), (-x).exp() * (1. + x)), "x {x}");
+        }
+        // Q(1/2, x) = erfc(sqrt(x))
+        for x in [0.2, 1., 4.] {
+            assert!((ln_gamma_q(0.5, x).exp() - libm::erfc(x.sqrt())).abs() < 1e-12, "x {x}");
+        }
+        assert_eq!(0., ln_gamma_q(3., 0.));
+        assert!((ln_gamma_q(1., 2000.) + 2000.).abs() < 1e-9);
+    }
+
+    /// Intervals from a gamma distribution with shape 2 and mean 200ms, i.e. the sum of two
+    /// exponentials with mean 100ms.
+    fn skewed_trace(n: usize) -> Vec<u64> {
+        let mut rng = StdRng::seed_from_u64(171);
+        (0..n).map(|_| {
+            let (u1, u2): (f64, f64) = (rng.gen::<f64>().max(f64::MIN_POSITIVE), rng.gen::<f64>().max(f64::MIN_POSITIVE));
+            (-100. * u1.ln() - 100. * u2.ln()) as u64
+        }).collect()
+    }
+
+    #[tokio::test]
+    async fn test_gamma_phi_is_monotonic() {
+        let detector = DetectorBuilder::new().distribution(Distribution::Gamma).build();
+        detector.insert(Local::now()).await.unwrap();
+        for ms in skewed_trace(200) {
+            detector.insert_after_ms(ms).await.unwrap();
+        }
+        let mut previous = 0.;
+        for elapsed in (0..5000).step_by(25) {
+            let phi = detector.phi_for_elapsed(TimeDelta::milliseconds(elapsed)).await.unwrap();
+            assert!(phi >= previous, "phi {phi} after {previous} at {elapsed}ms");
+            previous = phi;
+        }
+        assert!(previous > 8.);
+    }
+
+    #[tokio::test]
+    async fn test_gamma_fits_skewed_trace() {
+        let normal = DetectorBuilder::new().build();
+        let gamma = DetectorBuilder::new().distribution(Distribution::Gamma).build();
+        let trace = skewed_trace(1000);
+        for detector in [&normal, &gamma] {
+            detector.insert(Local::now()).await.unwrap();
+            for ms in &trace {
+                detector.insert_after_ms(*ms).await.unwrap();
+            }
+        }
+        // heartbeats of the trace itself that each model would have suspected before they arrived
+        let mut false_suspicions = [0, 0];
+        for ms in &trace {
+            let elapsed = TimeDelta::milliseconds(*ms as i64);
+            for (i, detector) in [&normal, &gamma].into_iter().enumerate() {
+                if detector.phi_for_elapsed(elapsed).await.unwrap() > 3. {
+                    false_suspicions[i] += 1;
+                }
+            }
+        }
+        assert!(false_suspicions[1] < false_suspicions[0], "{false_suspicions:?}");
+        assert!(false_suspicions[1] <= 3, "{false_suspicions:?}");
+    }
+
+    #[tokio::test]
+    async fn test_invariants_hold_across_operations() {
+        let detector = DetectorBuilder::new().window_length(5).build();
+        detector.verify_invariants().await.unwrap();
+        let start = Local::now().sub(Duration::seconds(10));
+        detector.insert(start).await.unwrap();
+        detector.verify_invariants().await.unwrap();
+        for ms in [100, 120, 80, 110, 90, 105, 95] {
+            detector.insert_after_ms(ms).await.unwrap();
+            detector.phi(Local::now()).await.unwrap();
+            detector.verify_invariants().await.unwrap();
+        }
+        let last = detector.last_arrived_at().await.unwrap();
+        detector.insert_gap(last.add(Duration::milliseconds(400)), 3).await.unwrap();
+        detector.verify_invariants().await.unwrap();
+        detector.apply_config(DetectorConfig { window_length: 3, ..detector.config().unwrap() }).await.unwrap();
+        detector.verify_invariants().await.unwrap();
+        detector.rebase_clock().await;
+        detector.verify_invariants().await.unwrap();
+
+        let replica = DetectorBuilder::new().window_length(2).build();
+        replica.warm_start_from(&detector).await.unwrap();
+        replica.verify_invariants().await.unwrap();
+        let seeded = Detector::from_statistics(DetectorConfig::default(), Statistics::from_moments(10, 100., 25., start).unwrap());
+        seeded.verify_invariants().await.unwrap();
+
+        detector.statistics.write().await.n = 10;
+        assert!(detector.verify_invariants().await.unwrap_err().contains("does not match"));
+    }
+
+    #[tokio::test]
+    async fn test_constant_phi_with_constant_pings_calculation() {
+        let stats = Statistics::new(10);
+        let detector = Detector {
+            statistics: RwLock::new(stats),
+            ..Detector::new(10)
+        };
+        let mut i = 0;
+        let mut curr_time = Local::now();
         while i <= 100 {
             let arrived_at = curr_time;
-            &detector.insert(arrived_at).await;
+            detector.insert(arrived_at).await.unwrap();
             curr_time = curr_time.add(Duration::milliseconds(10));
             i += 10;
         }
-        let (mut variance, mut mean) = detector.variance_and_mean().await.unwrap();
-        mean = (mean * 100.0).round() * 0.01;
-        variance = (variance * 100.0).round() * 0.01;
-        assert_eq!(10., mean);
-        assert_eq!(0., variance);
-        curr_time = curr_time.add(Duration::milliseconds(10));
-        assert_eq!(0., detector.phi(curr_time).await.unwrap());
+        let (mut variance, mut mean) = detector.variance_and_mean().await.unwrap();
+        mean = (mean * 100.0).round() * 0.01;
+        variance = (variance * 100.0).round() * 0.01;
+        assert_eq!(10., mean);
+        assert_eq!(0., variance);
+        assert_eq!(0., detector.phi(curr_time).await.unwrap());
+        curr_time = curr_time.add(Duration::milliseconds(10));
+        assert_eq!(crate::DEFAULT_MAX_PHI, detector.phi(curr_time).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_phi_of_constant_pings_is_monotone() {
+        let detector = Detector::new(10);
+        let start = Local::now();
+        for i in 0..10 {
+            detector.insert(start.add(Duration::milliseconds(i * 10))).await.unwrap();
+        }
+        let last = start.add(Duration::milliseconds(90));
+        let mut previous = 0.;
+        for ms in 0..40 {
+            let phi = detector.phi(last.add(Duration::milliseconds(ms))).await.unwrap();
+            assert!(phi >= previous, "{ms}: {phi} < {previous}");
+            previous = phi;
+        }
+        let now = last.add(Duration::milliseconds(100));
+        assert!(detector.sustained_over_threshold(8., Duration::milliseconds(89), now).await.unwrap());
+        assert!(!detector.sustained_over_threshold(8., Duration::milliseconds(90), now).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_smoothed_phi_is_less_jittery_than_raw_phi() {
+        let detector = Detector::with_phi_smoothing(10, 0.2);
+        let mut curr_time = Local::now();
+        for interval in [100, 120, 80, 110, 90, 105, 95, 100] {
+            detector.insert(curr_time).await.unwrap();
+            curr_time = curr_time.add(Duration::milliseconds(interval));
+        }
+        detector.insert(curr_time).await.unwrap();
+
+        let mut raw = vec![];
+        let mut smoothed = vec![];
+        for offset in [60, 110, 70, 120, 65, 115, 75, 105] {
+            let t = curr_time.add(Duration::milliseconds(offset));
+            raw.push(detector.phi(t).await.unwrap());
+            smoothed.push(detector.smoothed_phi(t).await.unwrap());
+        }
+        let jitter = |values: &[f64]| values.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>();
+        assert!(jitter(&smoothed) < jitter(&raw));
+    }
+
+    #[tokio::test]
+    async fn test_insert_gap_matches_clean_stream() {
+        let clean = Detector::new(10);
+        let split = Detector::with_gap_handling(10, GapHandling::Split);
+        let discard = Detector::with_gap_handling(10, GapHandling::Discard);
+        let start = Local::now();
+        for i in 0..=8 {
+            clean.insert(start.add(Duration::milliseconds(i * 100))).await.unwrap();
+        }
+        for i in [0, 1, 2, 3, 4] {
+            split.insert(start.add(Duration::milliseconds(i * 100))).await.unwrap();
+            discard.insert(start.add(Duration::milliseconds(i * 100))).await.unwrap();
+        }
+        split.insert_gap(start.add(Duration::milliseconds(800)), 3).await.unwrap();
+        discard.insert_gap(start.add(Duration::milliseconds(800)), 3).await.unwrap();
+
+        let clean_stats = clean.statistics.read().await;
+        let split_stats = split.statistics.read().await;
+        let discard_stats = discard.statistics.read().await;
+        assert_eq!(clean_stats.arrival_intervals, split_stats.arrival_intervals);
+        assert_eq!(clean_stats.n, split_stats.n);
+        assert_eq!(vec![100, 100, 100, 100], discard_stats.arrival_intervals[..]);
+        assert_eq!(clean_stats.last_arrived_at, split_stats.last_arrived_at);
+        assert_eq!(clean_stats.last_arrived_at, discard_stats.last_arrived_at);
+    }
+
+    #[tokio::test]
+    async fn test_insert_gap_out_of_order_and_rate_limited() {
+        let detector = DetectorBuilder::new().min_accept_interval(TimeDelta::milliseconds(50)).build();
+        let start = Local::now();
+        for ms in [0, 100, 200, 300, 320] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let error = detector.insert_gap(start.add(Duration::milliseconds(250)), 3).await.unwrap_err();
+        assert_eq!(Some(&PhiError::UnorderedArrivals { index: 4 }), error.downcast_ref::<PhiError>());
+        detector.insert_gap(start.add(Duration::milliseconds(330)), 3).await.unwrap();
+        assert_eq!(2, detector.rate_limited().await);
+        assert_eq!(vec![100; 3], detector.statistics.read().await.arrival_intervals[..]);
+
+        // split from the last accepted heartbeat at 300, not the rate-limited one at 330
+        detector.insert_gap(start.add(Duration::milliseconds(700)), 3).await.unwrap();
+        assert_eq!(vec![100; 7], detector.statistics.read().await.arrival_intervals[..]);
+        let overflow = detector.insert_gap(start.add(Duration::days(120)), 1).await.unwrap_err();
+        assert!(matches!(overflow.downcast_ref::<PhiError>(), Some(PhiError::IntervalOverflow { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_phi_is_monotone_for_near_zero_mean() {
+        let detector = Detector::new(10);
+        let start = Local::now();
+        let mut curr_time = start;
+        detector.insert(curr_time).await.unwrap();
+        for interval in [0, 0, 5, 0] {
+            curr_time = curr_time.add(Duration::milliseconds(interval));
+            detector.insert(curr_time).await.unwrap();
+        }
+        let (variance, mean) = detector.variance_and_mean().await.unwrap();
+        assert_eq!(1.25, mean);
+        assert!(variance > 0.);
+
+        let mut previous = 0.;
+        for delta in -5..30 {
+            let phi = detector.phi(curr_time.add(Duration::milliseconds(delta))).await.unwrap();
+            assert!(phi.is_sign_positive());
+            assert!(phi >= previous, "phi decreased at delta {}: {} < {}", delta, phi, previous);
+            previous = phi;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_healthier_of_selects_lower_phi() {
+        let fast = Detector::new(10);
+        let slow = Detector::new(10);
+        let start = Local::now();
+        for (fast_at, slow_at) in [(0, 0), (90, 900), (210, 2100), (300, 3000)] {
+            fast.insert(start.add(Duration::milliseconds(fast_at))).await.unwrap();
+            slow.insert(start.add(Duration::milliseconds(slow_at))).await.unwrap();
+        }
+        let t = start.add(Duration::milliseconds(3500));
+        assert!(std::ptr::eq(&slow, healthier_of(&fast, &slow, t).await.unwrap()));
+        assert!(std::ptr::eq(&slow, healthier_of(&slow, &fast, t).await.unwrap()));
+
+        let twin = Detector::new(10);
+        for at in [0, 900, 2100, 3000] {
+            twin.insert(start.add(Duration::milliseconds(at))).await.unwrap();
+        }
+        assert!(std::ptr::eq(&twin, healthier_of(&twin, &slow, t).await.unwrap()));
+        assert!(std::ptr::eq(&slow, healthier_of(&slow, &twin, t).await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_arrival_log() {
+        let detector = DetectorBuilder::new()
+            .min_heartbeat_spacing_ms(10)
+            .min_accept_interval(TimeDelta::milliseconds(50))
+            .with_arrival_log(3)
+            .build();
+        let start = Local::now();
+        let at = |ms| start.add(Duration::milliseconds(ms));
+        for ms in [0, 100, 105, 130, 200] {
+            detector.insert(at(ms)).await.unwrap();
+        }
+        let expected = vec![
+            ArrivalLogEntry { arrived_at: at(105), rejection: Some(Rejection::Debounced) },
+            ArrivalLogEntry { arrived_at: at(130), rejection: Some(Rejection::RateLimited) },
+            ArrivalLogEntry { arrived_at: at(200), rejection: None },
+        ];
+        assert_eq!(expected, detector.arrival_log().await);
+        assert!(format!("{detector:?}").contains("RateLimited"));
+
+        let disabled = Detector::new(10);
+        disabled.insert(start).await.unwrap();
+        assert!(disabled.arrival_log().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mean_absolute_deviation() {
+        let detector = Detector::new(10);
+        assert!(detector.mean_absolute_deviation().await.is_err());
+        detector.insert(Local::now()).await.unwrap();
+        for ms in [100, 120, 80, 100] {
+            detector.insert_after_ms(ms).await.unwrap();
+        }
+        assert!((detector.mean_absolute_deviation().await.unwrap() - 10.).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_severity_follows_reconfigured_bands() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        detector.insert(start).await.unwrap();
+        for _ in 0..10 {
+            detector.insert_after_ms(100).await.unwrap();
+        }
+        let t = detector.last_arrived_at().await.unwrap().add(Duration::milliseconds(100));
+        assert_eq!(0, detector.severity(t).await.unwrap().level);
+        let late = t.add(Duration::milliseconds(100));
+        assert_eq!("dead", detector.severity(late).await.unwrap().name);
+
+        let config = DetectorConfig { severity_bands: SeverityBands::new("up", &[(0., "down")]).unwrap(), ..detector.config().unwrap() };
+        detector.apply_config(config).await.unwrap();
+        assert_eq!(Severity { level: 0, name: "up".to_string() }, detector.severity(t.sub(Duration::milliseconds(100))).await.unwrap());
+        assert_eq!("down", detector.severity(t).await.unwrap().name);
+    }
+
+    #[test]
+    fn test_statistics_try_from_intervals() {
+        let statistics = Statistics::try_from((3, &[90, 100, 110, 120][..])).unwrap();
+        assert_eq!([100, 110, 120], statistics.arrival_intervals[..]);
+        assert_eq!(4, statistics.n);
+        assert_eq!(110., statistics.mean());
+
+        assert_eq!(Some(PhiError::EmptyWindow), Statistics::try_from((3, &[][..])).err());
+        assert!(matches!(Statistics::try_from((0, &[100][..])), Err(PhiError::InvalidConfig(_))));
+        assert!(matches!(Statistics::try_from((3, &[u64::MAX, 1][..])), Err(PhiError::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_insert_with_incarnation() {
+        let detector = Detector::new(10);
+        let start = Local::now();
+        let at = |ms| start.add(Duration::milliseconds(ms));
+        for (ms, incarnation) in [(0, 1), (100, 1), (200, 1)] {
+            assert!(!detector.insert_with_incarnation(at(ms), incarnation).await.unwrap());
+        }
+        assert!(detector.insert_with_incarnation(at(5000), 2).await.unwrap());
+        assert_eq!(1, detector.restarts().await);
+        assert!(detector.statistics.read().await.arrival_intervals.is_empty());
+
+        let stale = detector.insert_with_incarnation(at(5100), 1).await.unwrap_err();
+        assert_eq!(Some(&PhiError::StaleIncarnation { current: 2, received: 1 }), stale.downcast_ref::<PhiError>());
+        for ms in [5030, 5060] {
+            assert!(!detector.insert_with_incarnation(at(ms), 2).await.unwrap());
+        }
+        assert_eq!([30, 30], detector.statistics.read().await.arrival_intervals[..]);
+        assert_eq!(10, detector.config().unwrap().window_length);
+    }
+
+    #[tokio::test]
+    async fn test_poisoned_config_lock() {
+        let poison = |detector: &Detector| {
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let _guard = detector.config.write().unwrap();
+                panic!("heartbeat callback panicked");
+            }));
+            assert!(detector.config.is_poisoned());
+        };
+        let start = Local::now();
+        let recovering = Detector::new(10);
+        recovering.insert(start).await.unwrap();
+        poison(&recovering);
+        recovering.insert(start.add(Duration::milliseconds(100))).await.unwrap();
+        assert!(recovering.phi(start.add(Duration::milliseconds(150))).await.is_ok());
+        assert!(recovering.try_phi(start).is_ok());
+        assert_eq!(10, recovering.config().unwrap().window_length);
+
+        let failing = DetectorBuilder::new().poison_policy(PoisonPolicy::Fail).build();
+        failing.insert(start).await.unwrap();
+        failing.insert(start.add(Duration::milliseconds(50))).await.unwrap();
+        poison(&failing);
+        let err = failing.insert(start.add(Duration::milliseconds(100))).await.unwrap_err();
+        assert_eq!(Some(&PhiError::Poisoned), err.downcast_ref::<PhiError>());
+        assert!(failing.phi(start).await.is_err());
+        assert_eq!(Err(TryError::Poisoned), failing.try_phi(start));
+        assert_eq!(Err(PhiError::Poisoned), failing.config());
+        assert!(failing.snapshot().await.is_err());
+        let summary = failing.shutdown().await;
+        assert_eq!((2, None), (summary.heartbeats, summary.mean_ms));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_until_suspected_after_outage() {
+        let detector = Arc::new(DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build());
+        let began = tokio::time::Instant::now();
+        let start = Local::now();
+        let sender = Arc::clone(&detector);
+        tokio::spawn(async move {
+            // heartbeats every 100ms for a second, then an outage
+            for ms in (0..1000).step_by(100) {
+                tokio::time::sleep_until(began + std::time::Duration::from_millis(ms)).await;
+                sender.insert(start.add(Duration::milliseconds(ms as i64))).await.unwrap();
+            }
+        });
+        let (phi, at) = detector.wait_until_suspected(8.).await.unwrap();
+        let waited = began.elapsed();
+        assert!(phi > 8.);
+        assert!(waited > std::time::Duration::from_millis(1000) && waited < std::time::Duration::from_millis(1100), "{waited:?}");
+        let last = detector.last_arrived_at().await.unwrap();
+        assert!(detector.phi(at.sub(Duration::milliseconds(2))).await.unwrap() <= 8.);
+        assert_eq!(start.add(Duration::milliseconds(900)), last);
+
+
+        // already suspected, so no time passes
+        let stale = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        for ms in [1000, 900, 800] {
+            stale.insert(Local::now().sub(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let again = tokio::time::Instant::now();
+        stale.wait_until_suspected(8.).await.unwrap();
+        assert_eq!(again, tokio::time::Instant::now());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dropped_wait_until_suspected_holds_nothing() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        detector.insert(start).await.unwrap();
+        detector.insert(start.add(Duration::milliseconds(100))).await.unwrap();
+        tokio::select! {
+            _ = detector.wait_until_suspected(50.) => panic!("resolved before the timeout"),
+            _ = tokio::time::sleep(std::time::Duration::from_millis(50)) => {}
+        }
+        assert!(detector.try_insert(start.add(Duration::milliseconds(200))).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_distribution_params_match_moments() {
+        let detector = Detector::new(10);
+        assert!(detector.distribution_params().await.is_err());
+        detector.insert(Local::now()).await.unwrap();
+        for ms in [100, 120, 80, 100] {
+            detector.insert_after_ms(ms).await.unwrap();
+        }
+        let (variance, mean) = detector.variance_and_mean().await.unwrap();
+        let params = detector.distribution_params().await.unwrap();
+        assert_eq!(DistributionParams { family: DistributionFamily::Normal, mean, std_dev: variance.sqrt() }, params);
+
+        detector.apply_config(DetectorConfig { distribution: Distribution::Gamma, ..detector.config().unwrap() }).await.unwrap();
+        let DistributionFamily::Gamma { shape, rate } = detector.distribution_params().await.unwrap().family else {
+            panic!("expected a gamma fit");
+        };
+        assert!((shape / rate - mean).abs() < 1e-9);
+        assert!((shape / (rate * rate) - variance).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_is_consistent() {
+        let detector = DetectorBuilder::new().window_length(8).min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        let idle = detector.evaluate(start, 8.).await.unwrap();
+        assert_eq!(Evaluation { phi: 0., available: true, sample_count: 0, confidence: 0. }, idle);
+        detector.insert(start).await.unwrap();
+        for _ in 0..4 {
+            detector.insert_after_ms(100).await.unwrap();
+        }
+        let last = detector.last_arrived_at().await.unwrap();
+        for elapsed in [50, 100, 150, 200, 400] {
+            let now = last.add(Duration::milliseconds(elapsed));
+            let evaluation = detector.evaluate(now, 8.).await.unwrap();
+            assert_eq!(detector.phi(now).await.unwrap(), evaluation.phi);
+            assert_eq!(evaluation.phi <= 8., evaluation.available);
+            assert_eq!(4, evaluation.sample_count);
+            assert_eq!(0.5, evaluation.confidence);
+        }
+        assert!(!detector.evaluate(last.add(Duration::milliseconds(400)), 8.).await.unwrap().available);
+    }
+
+    #[tokio::test]
+    async fn test_reservoir_represents_entire_history() {
+        let detector = DetectorBuilder::new().window_length(200).eviction_policy(EvictionPolicy::Reservoir).build();
+        let mut rng = StdRng::seed_from_u64(7);
+        detector.insert(Local::now()).await.unwrap();
+        // the cadence drifts from 100ms to 300ms, averaging 200ms over the whole stream
+        let count = 20000;
+        for i in 0..count {
+            let base = 100 + 200 * i / count;
+            detector.insert_after_ms(base + rng.gen_range(0..10)).await.unwrap();
+        }
+        let (_, mean) = detector.variance_and_mean().await.unwrap();
+        assert_eq!(200, detector.statistics.read().await.arrival_intervals.len());
+        assert!((mean - 204.5).abs() < 20., "{mean}");
+    }
+
+    #[tokio::test]
+    async fn test_replace_statistics() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        for ms in [0, 1000, 2000, 3000] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let arrivals: Vec<_> = (0..5).map(|i| start.add(Duration::milliseconds(i * 100))).collect();
+        let replacement = Statistics::rebuild(&arrivals, 10).unwrap();
+        let t = start.add(Duration::milliseconds(1000));
+        let expected = Detector::from_statistics(detector.config().unwrap(), replacement.clone()).phi(t).await.unwrap();
+        detector.replace_statistics(replacement).await.unwrap();
+
+        assert_eq!([100, 100, 100, 100], detector.statistics.read().await.arrival_intervals[..]);
+        assert_eq!(arrivals[4], detector.last_arrived_at().await.unwrap());
+        assert_eq!(expected, detector.phi(t).await.unwrap());
+        assert_eq!(detector.config().unwrap().max_phi, detector.phi(t).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unhealthy_heartbeats_raise_adjusted_phi() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        for i in 0..10 {
+            detector.insert_with_status(start.add(Duration::milliseconds(i * 100)), true).await.unwrap();
+        }
+        let t = start.add(Duration::milliseconds(1000));
+        assert_eq!(detector.phi(t).await.unwrap(), detector.adjusted_phi(t).await.unwrap());
+
+        for i in 10..20 {
+            detector.insert_with_status(start.add(Duration::milliseconds(i * 100)), false).await.unwrap();
+        }
+        let t = start.add(Duration::milliseconds(2000));
+        let phi = detector.phi(t).await.unwrap();
+        let adjusted = detector.adjusted_phi(t).await.unwrap();
+        assert_eq!(10, detector.unhealthy_heartbeats().await);
+        assert!((adjusted - phi - 0.5 * crate::DEFAULT_THRESHOLD).abs() < 1e-9);
+        assert!(phi < crate::DEFAULT_THRESHOLD);
+    }
+
+    #[test]
+    fn test_try_build_checks_min_samples_fit_window() {
+        let err = DetectorBuilder::new().window_length(5).min_samples(6).try_build().unwrap_err();
+        assert!(matches!(&err, PhiError::InvalidConfig(reason) if reason.contains("min_samples (6)")));
+        assert!(DetectorBuilder::new().window_length(5).min_samples(5).try_build().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_replay_script() {
+        let start = Local::now();
+        let at = |ms| start.add(Duration::milliseconds(ms));
+        let script = [
+            Event::Query(at(0)),
+            Event::Heartbeat(at(0)),
+            Event::Heartbeat(at(100)),
+            Event::Heartbeat(at(200)),
+            Event::Query(at(200)),
+            Event::Query(at(300)),
+            Event::Heartbeat(at(300)),
+            Event::Query(at(350)),
+        ];
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let phis = detector.replay(&script).await.unwrap();
+
+        let reference = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        for ms in [0, 100, 200] {
+            reference.insert(at(ms)).await.unwrap();
+        }
+        let (at_200, at_300) = (reference.phi(at(200)).await.unwrap(), reference.phi(at(300)).await.unwrap());
+        reference.insert(at(300)).await.unwrap();
+        let expected = vec![None, Some(at_200), Some(at_300), Some(reference.phi(at(350)).await.unwrap())];
+        assert_eq!(expected, phis);
+        assert!((at_300 + 0.5f64.log10()).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_last_computed_phi() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        assert_eq!(None, detector.last_computed_phi());
+        let start = Local::now();
+        for ms in [0, 100, 200] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        for ms in [250, 300, 280] {
+            let t = start.add(Duration::milliseconds(ms));
+            let phi = detector.phi(t).await.unwrap();
+            let (last, at) = detector.last_computed_phi().unwrap();
+            assert_eq!(phi, last);
+            assert_eq!(t.timestamp_micros(), at.timestamp_micros());
+        }
+    }
+
+    #[cfg(feature = "tdigest")]
+    #[tokio::test]
+    async fn test_tdigest_tail_quantiles() {
+        let detector = Detector::new(100);
+        assert!(detector.quantile(0.99).await.is_err());
+        assert!(detector.quantile(1.5).await.is_err());
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut intervals: Vec<u64> = (0..100_000)
+            .map(|_| 1 + (-(1. - rng.gen::<f64>()).ln() * 100.) as u64)
+            .collect();
+        detector.insert(Local::now()).await.unwrap();
+        for ms in &intervals {
+            detector.insert_after_ms(*ms).await.unwrap();
+        }
+        intervals.sort_unstable();
+        for q in [0.5, 0.99, 0.999] {
+            let exact = intervals[(q * intervals.len() as f64) as usize] as f64;
+            let estimate = detector.quantile(q).await.unwrap();
+            assert!((estimate - exact).abs() / exact < 0.05, "q {q}: {estimate} vs {exact}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_change_point_detection() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let stable = Detector::new(40);
+        stable.insert(Local::now()).await.unwrap();
+        assert!(stable.change_point_detected(0.01).await.is_err());
+        for _ in 0..40 {
+            stable.insert_after_ms(rng.gen_range(95..105)).await.unwrap();
+        }
+        assert!(!stable.change_point_detected(0.01).await.unwrap());
+        assert!(stable.change_point_detected(1.).await.is_err());
+
+        let stepped = Detector::new(40);
+        stepped.insert(Local::now()).await.unwrap();
+        for i in 0..40 {
+            let base = if i < 20 { 100 } else { 110 };
+            stepped.insert_after_ms(base + rng.gen_range(0..10)).await.unwrap();
+        }
+        assert!(stepped.change_point_detected(0.01).await.unwrap());
+    }
+
+    #[cfg(feature = "compact")]
+    #[tokio::test]
+    async fn test_compact_window_matches_full_width_phi() {
+        let config = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).config().clone();
+        let detector = Detector::from_config(config.clone());
+        let start = Local::now();
+        detector.insert(start).await.unwrap();
+        let intervals = [1000u64, 1200, 900, 1100, 60_000, 1000];
+        for ms in intervals {
+            detector.insert_after_ms(ms).await.unwrap();
+        }
+        let mean = intervals.iter().sum::<u64>() as f64 / intervals.len() as f64;
+        let variance = intervals.iter().map(|v| (*v as f64 - mean).powi(2)).sum::<f64>() / intervals.len() as f64;
+        let last = detector.last_arrived_at().await.unwrap();
+        let full_width = Detector::from_statistics(config, Statistics::from_moments(10, mean, variance, last).unwrap());
+        for elapsed in [500, 5000, 20_000] {
+            let t = last.add(Duration::milliseconds(elapsed));
+            assert!((full_width.phi(t).await.unwrap() - detector.phi(t).await.unwrap()).abs() < 1e-9);
+        }
+
+        detector.insert_after_ms(u32::MAX as u64 + 1000).await.unwrap();
+        assert_eq!(Some(&u32::MAX), detector.statistics.read().await.arrival_intervals.last());
+    }
+
+    #[tokio::test]
+    async fn test_liveness_probability_decreases_with_delay() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        for ms in [0, 100, 210, 300] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let mut previous = f64::INFINITY;
+        for elapsed in (0..=300).step_by(20) {
+            let t = start.add(Duration::milliseconds(300 + elapsed));
+            let probability = detector.liveness_probability(t).await.unwrap();
+            assert!((0. ..=1.).contains(&probability));
+            assert!(probability <= previous);
+            previous = probability;
+        }
+        assert!(previous < 1e-8);
+        let at_mean = detector.liveness_probability(start.add(Duration::milliseconds(400))).await.unwrap();
+        assert!((at_mean - 0.5).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_decay_kernels_weight_recent_intervals() {
+        let config = DetectorBuilder::new().window_length(10);
+        let expectations = [
+            (DecayKernel::Uniform, 200.),
+            // weights 1/3, 2/3, 1 over 100, 200, 300
+            (DecayKernel::Linear, (100. / 3. + 400. / 3. + 300.) / 2.),
+            // weights e^-2, e^-1, 1
+            (DecayKernel::Exponential(1.), (100. * (-2f64).exp() + 200. * (-1f64).exp() + 300.) / ((-2f64).exp() + (-1f64).exp() + 1.)),
+        ];
+        for (kernel, expected) in expectations {
+            let detector = config.clone().decay_kernel(kernel).build();
+            detector.insert(Local::now()).await.unwrap();
+            for ms in [100, 200, 300] {
+                detector.insert_after_ms(ms).await.unwrap();
+            }
+            let (variance, mean) = detector.variance_and_mean().await.unwrap();
+            assert!((mean - expected).abs() < 1e-9, "{kernel:?}: {mean} vs {expected}");
+            assert!(variance > 0.);
+            if kernel != DecayKernel::Uniform {
+                assert!(mean > 200.);
+            }
+        }
+        let invalid = DetectorConfig { decay_kernel: DecayKernel::Exponential(-1.), ..DetectorConfig::default() };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_evaluate_uses_pre_insert_statistics() {
+        let detector = DetectorBuilder::new().window_length(4).min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        for ms in [0, 100, 200, 300] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let late = start.add(Duration::milliseconds(600));
+        let expected = detector.evaluate(late, 8.).await.unwrap();
+        let evaluation = detector.insert_and_evaluate(late, 8.).await.unwrap();
+        assert_eq!(expected, evaluation);
+        assert!(!evaluation.available);
+        assert_eq!(3, evaluation.sample_count);
+        assert_eq!(4, detector.evaluate(late, 8.).await.unwrap().sample_count);
+        assert_eq!(late, detector.last_arrived_at().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_mean_of_large_window_is_exact() {
+        let intervals: Vec<Interval> = (0..200_000).map(|i| 4_000_000_001 + 2 * (i % 2)).collect();
+        let reference = 4_000_000_002.;
+        let per_term: f64 = intervals.iter().map(|v| *v as f64 / intervals.len() as f64).sum();
+        assert_eq!(reference, mean_of(&intervals));
+        assert!((per_term - reference).abs() > 0.);
+        let intervals: Vec<u64> = intervals.iter().map(|v| interval_ms(*v)).collect();
+        let statistics = Statistics::try_from((200_000, &intervals[..])).unwrap();
+        let detector = Detector::from_statistics(DetectorConfig { window_length: 200_000, ..DetectorConfig::default() }, statistics);
+        assert_eq!(reference, detector.variance_and_mean().await.unwrap().1);
+    }
+
+    #[tokio::test]
+    async fn test_spread_of_window_is_divided_once() {
+        let detector = Detector::new(10);
+        detector.insert(Local::now()).await.unwrap();
+        for ms in 100..=105 {
+            detector.insert_after_ms(ms).await.unwrap();
+        }
+        // 35 / 12 and 9 / 6, one ulp off when every term is divided by 6 before summing
+        assert_eq!((35. / 12., 102.5), detector.variance_and_mean().await.unwrap());
+        assert_eq!(1.5, detector.mean_absolute_deviation().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeats_until_confident() {
+        let detector = DetectorBuilder::new().window_length(10).build();
+        assert_eq!(10, detector.heartbeats_until_confident(1.).await);
+        let start = Local::now();
+        for ms in [0, 100, 200, 300, 400] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        assert_eq!(6, detector.heartbeats_until_confident(1.).await);
+        assert_eq!(1, detector.heartbeats_until_confident(0.45).await);
+        assert_eq!(0, detector.heartbeats_until_confident(0.4).await);
+    }
+
+    #[tokio::test]
+    async fn test_near_zero_std_dev_is_treated_as_point_mass() {
+        let build = |epsilon| DetectorBuilder::new().decay_kernel(DecayKernel::Linear).std_dev_epsilon(epsilon).build();
+        let (detector, unguarded) = (build(crate::DEFAULT_STD_DEV_EPSILON), build(0.));
+        let point_mass = Detector::new(20);
+        let start = Local::now();
+        for i in 0..20 {
+            for d in [&detector, &unguarded, &point_mass] {
+                d.insert(start.add(Duration::milliseconds(i * 100))).await.unwrap();
+            }
+        }
+        let (variance, _) = detector.variance_and_mean().await.unwrap();
+        assert!(variance > 0. && variance.sqrt() < 1e-9, "{variance}");
+        assert_eq!(0., point_mass.variance_and_mean().await.unwrap().0);
+        let t = start.add(Duration::milliseconds(2050));
+        assert_eq!(crate::DEFAULT_MAX_PHI, unguarded.phi(t).await.unwrap());
+        assert_eq!(point_mass.phi(t).await.unwrap(), detector.phi(t).await.unwrap());
+        // a point mass at the mean: not late up to it, certainly late past it
+        for (ms, phi) in [(1950, 0.), (1999, 0.), (2001, crate::DEFAULT_MAX_PHI)] {
+            assert_eq!(phi, detector.phi(start.add(Duration::milliseconds(ms))).await.unwrap(), "{ms}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_std_dev_epsilon_snaps_spread_to_point_mass() {
+        let (guarded, unguarded) = (DetectorBuilder::new().std_dev_epsilon(5.).build(), Detector::new(20));
+        for detector in [&guarded, &unguarded] {
+            detector.insert(Local::now()).await.unwrap();
+            for i in 0..20 {
+                detector.insert_after_ms(if i % 2 == 0 { 98 } else { 102 }).await.unwrap();
+            }
+        }
+        let (variance, mean) = unguarded.variance_and_mean().await.unwrap();
+        assert!((variance.sqrt() - 2.).abs() < 1e-9 && mean == 100., "{variance} {mean}");
+        for (elapsed, guarded_phi) in [(99, 0.), (101, crate::DEFAULT_MAX_PHI)] {
+            let elapsed = TimeDelta::milliseconds(elapsed);
+            let phi = unguarded.phi_for_elapsed(elapsed).await.unwrap();
+            assert!(phi > 0.1 && phi < 1., "{phi}");
+            assert_eq!(guarded_phi, guarded.phi_for_elapsed(elapsed).await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_csv() {
+        let detector = DetectorBuilder::new().build();
+        let start = Local::now();
+        for ms in [0, 100, 250, 300] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let mut csv = vec![];
+        detector.export_csv(&mut csv).await.unwrap();
+        assert_eq!("index,interval_ms\n0,100\n1,150\n2,50\n", String::from_utf8(csv).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_fork_is_independent() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        for ms in [0, 100, 200] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let fork = detector.fork().await.unwrap();
+        let t = start.add(Duration::milliseconds(350));
+        assert_eq!(detector.phi(t).await.unwrap(), fork.phi(t).await.unwrap());
+        fork.insert(start.add(Duration::milliseconds(340))).await.unwrap();
+        assert_eq!(3, fork.evaluate(t, 8.).await.unwrap().sample_count);
+        assert_eq!(2, detector.evaluate(t, 8.).await.unwrap().sample_count);
+        assert_eq!(start.add(Duration::milliseconds(200)), detector.last_arrived_at().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_stored_threshold_classifies_per_detector() {
+        let build = |threshold| DetectorBuilder::new().threshold(threshold).min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let (strict, lenient) = (build(1.), build(8.));
+        let start = Local::now();
+        for ms in [0, 100, 200, 300] {
+            strict.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+            lenient.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let t = start.add(Duration::milliseconds(415));
+        let phi = strict.phi(t).await.unwrap();
+        assert!(phi > 1. && phi <= 8., "{phi}");
+        assert_eq!(phi, lenient.phi(t).await.unwrap());
+        assert!(!strict.is_available_default(t).await.unwrap());
+        assert!(lenient.is_available_default(t).await.unwrap());
+        assert!(matches!(strict.status(t).await.unwrap(), NodeStatus::Suspected(_)));
+        assert!(matches!(lenient.status(t).await.unwrap(), NodeStatus::Healthy(_)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_insert_phi_and_reset() {
+        let detector = Arc::new(DetectorBuilder::new().window_length(50).min_std_deviation(TimeDelta::milliseconds(10)).build());
+        let start = Local::now();
+        let mut tasks = vec![];
+        for task in 0..4 {
+            let detector = Arc::clone(&detector);
+            tasks.push(tokio::spawn(async move {
+                for i in 0..300i64 {
+                    let at = start.add(Duration::milliseconds(i * 100 + task));
+                    match (task, i % 10) {
+                        (0, _) | (1, _) => detector.insert(at).await.unwrap(),
+                        (2, 0) => detector.reset().await.unwrap(),
+                        _ => {
+                            let phi = detector.phi(at).await.unwrap();
+                            assert!((0. ..=crate::DEFAULT_MAX_PHI).contains(&phi));
+                            detector.status(at).await.unwrap();
+                            detector.smoothed_phi(at).await.unwrap();
+                        }
+                    }
+                    let stats = detector.statistics.read().await;
+                    assert!(stats.arrival_intervals.len() <= 50);
+                    if stats.n == 0 {
+                        assert!(stats.arrival_intervals.is_empty());
+                    } else {
+                        assert_eq!(stats.n as usize, stats.arrival_intervals.len() + 1);
+                    }
+                }
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        detector.reset().await.unwrap();
+        assert_eq!(NodeStatus::Idle, detector.status(Local::now()).await.unwrap());
+        assert!(detector.statistics.read().await.arrival_intervals.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_quantile_within_budget() {
+        let detector = DetectorBuilder::new().build();
+        let start = Local::now();
+        let mut at = 0;
+        detector.insert(start).await.unwrap();
+        for i in 0..100 {
+            at += if i < 94 { 100 } else { 500 };
+            detector.insert(start.add(Duration::milliseconds(at))).await.unwrap();
+        }
+        assert!(detector.quantile_within(0.94, 100).await.unwrap());
+        assert!(!detector.quantile_within(0.95, 100).await.unwrap());
+        assert!(detector.quantile_within(0.95, 500).await.unwrap());
+        assert!(detector.quantile_within(1.5, 500).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_phi_saturation() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        for ms in [0, 100, 200, 300] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let phi = |ms| detector.phi(start.add(Duration::milliseconds(300 + ms)));
+        let mut flat = 100;
+        while phi(flat).await.unwrap() < phi(flat + 1000).await.unwrap() {
+            flat += 1;
+        }
+        assert!(detector.is_phi_saturated(start.add(Duration::milliseconds(300 + flat))).await.unwrap());
+        assert!(!detector.is_phi_saturated(start.add(Duration::milliseconds(300 + flat - 1))).await.unwrap());
+        assert_eq!(crate::DEFAULT_MAX_PHI, phi(flat).await.unwrap());
+    }
+
+    #[test]
+    fn test_window_length_for_memory_fits_budget() {
+        let fixed = std::mem::size_of::<Statistics>();
+        assert_eq!(0, window_length_for_memory(fixed / 2));
+        for budget in [fixed + 7, 4096, 1 << 20] {
+            let window_length = window_length_for_memory(budget) as usize;
+            let size = std::mem::size_of::<Interval>();
+            assert!(fixed + window_length * size <= budget);
+            assert!(fixed + (window_length + 1) * size > budget);
+        }
+    }
+
+    #[test]
+    fn test_phi_of_very_late_heartbeat_is_accurate() {
+        // -log10(P(Z > 10)), computed at 40 digits
+        let reference = 23.118_053_405_486_076;
+        let phi = crate::phi_from_params(200., 100., 10., Distribution::Normal);
+        assert!((phi - reference).abs() < 1e-9, "{phi}");
+        let cancelled = -(1. - (0.5 + 0.5 * libm::erf(10. / std::f64::consts::SQRT_2))).log10();
+        assert!(cancelled.is_infinite());
+    }
+
+    #[tokio::test]
+    async fn test_time_since_last() {
+        let detector = DetectorBuilder::new().build();
+        let error = detector.time_since_last(Local::now()).await.unwrap_err();
+        assert_eq!(Some(&PhiError::NoHeartbeat), error.downcast_ref::<PhiError>());
+        let start = Local::now().sub(Duration::seconds(1));
+        detector.insert(start).await.unwrap();
+        detector.insert(start.add(Duration::milliseconds(100))).await.unwrap();
+        let gap = detector.time_since_last(start.add(Duration::milliseconds(350))).await.unwrap();
+        assert_eq!(TimeDelta::milliseconds(250), gap);
+        detector.insert(Local::now()).await.unwrap();
+        let since = detector.time_since_last_now().await.unwrap();
+        assert!(since >= TimeDelta::zero() && since < TimeDelta::seconds(1));
+    }
+
+    #[test]
+    fn test_student_t_tail() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9 * b;
+        for z in [0.5, 1., 10., 1e4] {
+            // one degree of freedom is the Cauchy distribution
+            assert!(close(ln_student_t_tail(z, 1.).exp(), 0.5 - z.atan() / std::f64::consts::PI), "z {z}");
+        }
+        assert!((ln_student_t_tail(2.776_445_105, 4.).exp() - 0.025).abs() < 1e-9);
+        assert!(close(ln_student_t_tail(-1., 1.).exp(), 0.75));
+        assert!(ln_student_t_tail(1e100, 3.).is_finite());
+    }
+
+    #[tokio::test]
+    async fn test_student_t_phi_is_conservative_for_small_windows() {
+        let trace = |len: usize| -> Vec<u64> { (0..len).map(|i| if i % 2 == 0 { 90 } else { 110 }).collect() };
+        let phis = |len: usize| async move {
+            let build = |distribution| Detector::from_statistics(
+                DetectorConfig { window_length: len as u32, distribution, ..DetectorConfig::default() },
+                Statistics::try_from((len as u32, &trace(len)[..])).unwrap(),
+            );
+            let (normal, student) = (build(Distribution::Normal), build(Distribution::StudentT));
+            let t = normal.last_arrived_at().await.unwrap().add(Duration::milliseconds(150));
+            (normal.phi(t).await.unwrap(), student.phi(t).await.unwrap())
+        };
+        let (normal, student) = phis(4).await;
+        assert!(student < normal, "{student} {normal}");
+        let (normal, student) = phis(1000).await;
+        assert!(student < normal && (normal - student) / normal < 0.1, "{student} {normal}");
+    }
+
+    #[test]
+    fn test_statistics_diff_across_insert() {
+        let start = Local::now();
+        let mut before = Statistics::new(3);
+        for ms in [0, 100, 300, 600] {
+            before.insert(start.add(Duration::milliseconds(ms)));
+        }
+        let mut after = before.clone();
+        after.insert(start.add(Duration::milliseconds(750)));
+        let diff = before.diff(&after);
+        assert_eq!(vec![150], diff.entered);
+        assert_eq!(vec![100], diff.left);
+        assert_eq!(0, diff.sample_count_delta);
+        assert!((diff.mean_delta - 50. / 3.).abs() < 1e-9);
+        let variance = |v: [f64; 3], mean: f64| v.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / 3.;
+        let expected = variance([200., 300., 150.], 650. / 3.) - variance([100., 200., 300.], 200.);
+        assert!((diff.variance_delta - expected).abs() < 1e-9);
+        assert_eq!(StatisticsDiff { mean_delta: 0., variance_delta: 0., sample_count_delta: 0, entered: vec![], left: vec![] }, after.diff(&after));
+    }
+
+    #[tokio::test]
+    async fn test_min_phi_floor() {
+        let build = |min_phi| DetectorBuilder::new().min_phi(min_phi).min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let (floored, plain) = (build(0.5), build(0.));
+        let start = Local::now();
+        for ms in [0, 100, 200, 300] {
+            floored.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+            plain.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let just_arrived = start.add(Duration::milliseconds(301));
+        assert!(plain.phi(just_arrived).await.unwrap() < 0.01);
+        assert_eq!(0.5, floored.phi(just_arrived).await.unwrap());
+        let late = start.add(Duration::milliseconds(420));
+        assert_eq!(plain.phi(late).await.unwrap(), floored.phi(late).await.unwrap());
+        assert!(DetectorBuilder::new().min_phi(200.).try_build().is_err());
+    }
+
+    #[test]
+    fn test_insert_reports_recorded_interval() {
+        let mut stats = Statistics::new(10);
+        stats.min_heartbeat_spacing_ms = Some(50);
+        let start = Local::now();
+        assert!(!stats.insert(start));
+        assert!(stats.insert(start.add(Duration::milliseconds(100))));
+        assert!(!stats.insert(start.add(Duration::milliseconds(120))));
+        assert_eq!(1, stats.arrival_intervals.len());
+    }
+
+    #[tokio::test]
+    async fn test_sustained_over_threshold() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let start = Local::now();
+        let at = |ms: i64| start.add(Duration::milliseconds(ms));
+        for ms in (0..=1000).step_by(100) {
+            detector.insert(at(ms)).await.unwrap();
+        }
+        let sustained = |ms: i64| detector.sustained_over_threshold(8., TimeDelta::milliseconds(500), at(ms));
+        assert!(detector.phi(at(1300)).await.unwrap() > 8.);
+        assert!(!sustained(1300).await.unwrap());
+
+        // the spike ends with the next heartbeat
+        detector.insert(at(1300)).await.unwrap();
+        assert!(!sustained(1900).await.unwrap());
+        assert!(sustained(2500).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_insert_duration_matches_timestamp_inserts() {
+        let start = Local::now();
+        let by_duration = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let by_timestamp = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        by_duration.insert(start).await.unwrap();
+        by_timestamp.insert(start).await.unwrap();
+        let mut curr_time = start;
+        for ms in [100, 120, 90, 110] {
+            curr_time = curr_time.add(Duration::milliseconds(ms));
+            by_timestamp.insert(curr_time).await.unwrap();
+            assert_eq!(curr_time, by_duration.insert_duration(std::time::Duration::from_millis(ms as u64)).await.unwrap());
+        }
+        let t = curr_time.add(Duration::milliseconds(150));
+        assert_eq!(by_timestamp.phi(t).await.unwrap(), by_duration.phi(t).await.unwrap());
+
+        let arrived_at = by_duration.insert_duration(std::time::Duration::from_micros(100_500)).await.unwrap();
+        assert_eq!(curr_time.add(Duration::microseconds(100_500)), arrived_at);
+    }
+
+    #[tokio::test]
+    async fn test_sensitivity_falls_with_variance() {
+        let steady = Detector::new(100);
+        let jittery = Detector::new(100);
+        assert_eq!(Some(&PhiError::EmptyWindow), steady.sensitivity().await.unwrap_err().downcast_ref());
+        for i in 0..100 {
+            steady.insert_after_ms(if i % 2 == 0 { 990 } else { 1010 }).await.unwrap();
+            jittery.insert_after_ms(if i % 2 == 0 { 800 } else { 1200 }).await.unwrap();
+        }
+        let (steady, jittery) = (steady.sensitivity().await.unwrap(), jittery.sensitivity().await.unwrap());
+        assert!(jittery > 0.);
+        assert!(steady > 10. * jittery);
+    }
+
+    #[tokio::test]
+    async fn test_suggested_backoff_grows_with_phi() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let mut last = Local::now();
+        for _ in 0..10 {
+            last = detector.insert_after_ms(100).await.unwrap();
+        }
+        let (base, max) = (std::time::Duration::from_millis(100), std::time::Duration::from_secs(2));
+        let backoff = |ms: i64| detector.suggested_backoff(last.add(Duration::milliseconds(ms)), base, max);
+        assert_eq!(base, backoff(50).await.unwrap());
+        let (soon, later) = (backoff(130).await.unwrap(), backoff(140).await.unwrap());
+        assert!(base < soon && soon < later && later < max);
+        assert_eq!(max, backoff(1000).await.unwrap());
+    }
+
+    #[cfg(feature = "timing")]
+    #[tokio::test]
+    async fn test_last_compute_duration_over_large_window() {
+        let detector = Detector::new(10_000);
+        assert_eq!(None, detector.last_compute_duration());
+        let mut last = Local::now();
+        for i in 0..10_000 {
+            last = detector.insert_after_ms(100 + i % 7).await.unwrap();
+        }
+        assert_eq!(None, detector.last_compute_duration());
+        detector.phi(last.add(Duration::milliseconds(100))).await.unwrap();
+        assert!(detector.last_compute_duration().unwrap() > std::time::Duration::ZERO);
+        detector.variance_and_mean().await.unwrap();
+        assert!(detector.last_compute_duration().unwrap() > std::time::Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_prewarm_fills_window() {
+        let detector = DetectorBuilder::new().window_length(50).min_std_deviation(TimeDelta::milliseconds(50)).build();
+        detector.insert_after_ms(300).await.unwrap();
+        detector.prewarm(100).await.unwrap();
+        let last = detector.last_arrived_at().await.unwrap();
+        assert!(Local::now().sub(last) < Duration::seconds(1));
+        assert_eq!((0., 100.), detector.variance_and_mean().await.unwrap());
+        assert_eq!(50, detector.intervals_summary().await.unwrap().count);
+        let phi = detector.phi(last.add(Duration::milliseconds(200))).await.unwrap();
+        assert!(phi > 1. && phi < 8.);
+    }
+
+    #[tokio::test]
+    async fn test_burstiness_flags_bursty_senders() {
+        let regular = Detector::new(100);
+        let bursty = Detector::new(100);
+        assert_eq!(Some(&PhiError::EmptyWindow), regular.burstiness().await.unwrap_err().downcast_ref());
+        for i in 0..60 {
+            regular.insert_after_ms(100 + i % 3).await.unwrap();
+            bursty.insert_after_ms(if i % 6 == 5 { 550 } else { 10 }).await.unwrap();
+        }
+        let (regular, bursty) = (regular.burstiness().await.unwrap(), bursty.burstiness().await.unwrap());
+        assert_eq!(100., mean_of(&[10, 10, 10, 10, 10, 550]));
+        assert!(regular < 0.01);
+        assert!(bursty > 1.);
+    }
+
+    #[tokio::test]
+    async fn test_insert_checked_rejects_absurd_interval() {
+        let detector = DetectorBuilder::new().max_interval(TimeDelta::days(1)).build();
+        let start = Local::now();
+        detector.insert_checked(start).await.unwrap();
+        detector.insert_checked(start.add(Duration::milliseconds(100))).await.unwrap();
+        let absurd = start.add(Duration::days(365 * 100));
+        let err = detector.insert_checked(absurd).await.unwrap_err();
+        assert_eq!(Some(&PhiError::IntervalOverflow { interval_ms: absurd.sub(start).num_milliseconds() - 100, max_ms: 86_400_000 }), err.downcast_ref());
+        assert_eq!(start.add(Duration::milliseconds(100)), detector.last_arrived_at().await.unwrap());
+        assert_eq!(1, detector.intervals_summary().await.unwrap().count);
+        assert!(DetectorConfig { max_interval: TimeDelta::zero(), ..DetectorConfig::default() }.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_phi_with_snapshot_matches_live_detector() {
+        let config = DetectorConfig { min_std_deviation: TimeDelta::milliseconds(10), ..DetectorConfig::default() };
+        let detector = Detector::from_config(config.clone());
+        let intervals = [100, 120, 90, 110, 105];
+        detector.insert(Local::now()).await.unwrap();
+        for ms in intervals {
+            detector.insert_after_ms(ms).await.unwrap();
+        }
+        let snapshot = Statistics::try_from((config.window_length, &intervals[..])).unwrap();
+        for delta in [50., 105., 180.] {
+            let live = detector.phi_for_elapsed(Duration::milliseconds(delta as i64)).await.unwrap();
+            assert_eq!(live, phi_with_snapshot(delta, &snapshot, &config));
+        }
+        assert_eq!(config.max_phi, phi_with_snapshot(1e300, &snapshot, &config));
+        assert_eq!(config.max_phi, phi_with_snapshot(f64::INFINITY, &snapshot, &config));
+        assert_eq!(0., phi_with_snapshot(-1e300, &snapshot, &config));
+    }
+
+    #[tokio::test]
+    async fn test_probation_after_recovery() {
+        let start = Local::now();
+        let at = |ms: i64| start.add(Duration::milliseconds(ms));
+        for (policy, healthy_at) in [(ProbationPolicy::For(TimeDelta::milliseconds(250)), 1900), (ProbationPolicy::Heartbeats(2), 1800)] {
+            let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).probation(policy).build();
+            for ms in (0..=1000).step_by(100) {
+                detector.insert(at(ms)).await.unwrap();
+            }
+            assert!(matches!(detector.status(at(1050)).await.unwrap(), NodeStatus::Healthy(_)));
+            assert!(matches!(detector.status(at(1500)).await.unwrap(), NodeStatus::Suspected(_)));
+
+            detector.insert(at(1600)).await.unwrap();
+            assert!(matches!(detector.status(at(1650)).await.unwrap(), NodeStatus::Probation(_)));
+            detector.insert(at(1700)).await.unwrap();
+            detector.insert(at(healthy_at)).await.unwrap();
+            assert!(matches!(detector.status(at(healthy_at + 10)).await.unwrap(), NodeStatus::Healthy(_)));
+        }
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        for ms in [0, 100, 200, 300, 900] {
+            detector.insert(at(ms)).await.unwrap();
+        }
+        assert!(matches!(detector.status(at(950)).await.unwrap(), NodeStatus::Healthy(_)));
+    }
+
+    #[tokio::test]
+    async fn test_mark_down_until_next_heartbeat() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let mut last = Local::now();
+        for _ in 0..10 {
+            last = detector.insert_after_ms(100).await.unwrap();
+        }
+        let t = last.add(Duration::milliseconds(50));
+        let phi = detector.phi(t).await.unwrap();
+        assert!(phi < 1.);
+        detector.mark_down().await;
+        assert_eq!(crate::DEFAULT_MAX_PHI, detector.phi(t).await.unwrap());
+        assert!(matches!(detector.status(t).await.unwrap(), NodeStatus::Suspected(_)));
+        assert_eq!(crate::DEFAULT_MAX_PHI, detector.phi_recent(t, 5).await.unwrap());
+        assert!(detector.is_overdue(t).await.unwrap());
+
+        let arrived_at = detector.insert_after_ms(100).await.unwrap();
+        assert_eq!(phi, detector.phi(arrived_at.add(Duration::milliseconds(50))).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_outage_excluded_from_window() {
+        let detector = DetectorBuilder::new().outage_handling(OutageHandling::Exclude(10.)).build();
+        for i in 0..20 {
+            detector.insert_after_ms(100 + i % 3 * 10).await.unwrap();
+        }
+        let before = detector.variance_and_mean().await.unwrap();
+        detector.insert_after_ms(60_000).await.unwrap();
+        assert_eq!(before, detector.variance_and_mean().await.unwrap());
+        assert_eq!(1, detector.outage_count().await);
+
+        detector.insert_after_ms(110).await.unwrap();
+        assert_eq!(20, detector.intervals_summary().await.unwrap().count);
+        assert_eq!(1, detector.outage_count().await);
+        assert!(DetectorBuilder::new().outage_handling(OutageHandling::Exclude(0.5)).try_build().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blended_variance_between_windows() {
+        let build = |weight| DetectorBuilder::new().window_length(60).variance_blend(10, weight).build();
+        let (short, blended, long) = (build(1.), build(0.5), build(0.));
+        let plain = Detector::new(60);
+        for i in 0..60 {
+            let ms = if i < 50 { 100 + i % 2 * 10 } else { 100 + i % 2 * 80 };
+            for detector in [&short, &blended, &long, &plain] {
+                detector.insert_after_ms(ms).await.unwrap();
+            }
+        }
+        let t = plain.last_arrived_at().await.unwrap().add(Duration::milliseconds(200));
+        let (short, blended, long) = (short.phi(t).await.unwrap(), blended.phi(t).await.unwrap(), long.phi(t).await.unwrap());
+        assert_eq!(plain.phi(t).await.unwrap(), long);
+        assert!(short < blended && blended < long);
+        assert!(DetectorBuilder::new().variance_blend(0, 0.5).try_build().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sorted_intervals() {
+        let detector = Detector::new(5);
+        assert!(detector.sorted_intervals().await.is_empty());
+        detector.insert(Local::now()).await.unwrap();
+        for ms in [300, 100, 500, 200, 100, 400] {
+            detector.insert_after_ms(ms).await.unwrap();
+        }
+        assert_eq!(vec![100, 100, 200, 400, 500], detector.sorted_intervals().await);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_summary() {
+        let detector = DetectorBuilder::new().window_length(3).build();
+        let summaries = Arc::new(std::sync::Mutex::new(vec![]));
+        let seen = Arc::clone(&summaries);
+        detector.on_shutdown(move |summary| seen.lock().unwrap().push(summary.clone()));
+        let first = Local::now();
+        detector.insert(first).await.unwrap();
+        let mut last = first;
+        for ms in [300, 100, 200, 100] {
+            last = detector.insert_after_ms(ms).await.unwrap();
+        }
+        let phi = detector.phi(last.add(Duration::milliseconds(50))).await.unwrap();
+        let summary = detector.shutdown().await;
+        assert_eq!(DetectorSummary {
+            heartbeats: 5,
+            sample_count: 3,
+            mean_ms: Some(400. / 3.),
+            std_dev_ms: Some((20000f64 / 9.).sqrt()),
+            last_arrived_at: Some(last),
+            last_phi: Some(phi),
+        }, summary);
+        assert_eq!(vec![summary], *summaries.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_phi_conservative_is_more_forgiving() {
+        let detector = DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let mut last = Local::now();
+        for i in 0..10 {
+            last = detector.insert_after_ms(100 + i % 2 * 20).await.unwrap();
+        }
+        let t = last.add(Duration::milliseconds(150));
+        let phi = detector.phi(t).await.unwrap();
+        assert_eq!(phi, detector.phi_conservative(t, 1.).await.unwrap());
+        let forgiving = detector.phi_conservative(t, 2.).await.unwrap();
+        assert!(forgiving < phi);
+        assert!(detector.phi_conservative(t, 4.).await.unwrap() < forgiving);
+        assert!(detector.phi_conservative(t, 0.).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_is_overdue() {
+        let detector = Detector::new(10);
+        let start = Local::now();
+        assert!(!detector.is_overdue(start).await.unwrap());
+        detector.insert(start).await.unwrap();
+        assert!(!detector.is_overdue(start.add(Duration::seconds(10))).await.unwrap());
+        for ms in [100, 200, 300] {
+            detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let last = start.add(Duration::milliseconds(300));
+        assert!(!detector.is_overdue(last.add(Duration::milliseconds(60))).await.unwrap());
+        assert!(!detector.is_overdue(last.add(Duration::milliseconds(100))).await.unwrap());
+        assert!(detector.is_overdue(last.add(Duration::milliseconds(101))).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_phi_with_n_counts_window() {
+        let detector = DetectorBuilder::new().window_length(10).min_std_deviation(TimeDelta::milliseconds(10)).build();
+        let mut last = Local::now();
+        for (heartbeats, expected_n) in [(4, 3), (11, 10)] {
+            for _ in 0..heartbeats {
+                last = detector.insert_after_ms(100).await.unwrap();
+            }
+            let t = last.add(Duration::milliseconds(120));
+            let (phi, n) = detector.phi_with_n(t).await.unwrap();
+            assert_eq!(expected_n, n);
+            assert_eq!(detector.phi(t).await.unwrap(), phi);
+        }
     }
 }
@@ -0,0 +1,86 @@
+use std::error::Error;
+use chrono::{DateTime, Local};
+use crate::{Detector, DetectorConfig, PhiError, PhiInteraction};
+
+/// Several windows of different lengths fed by the same heartbeats, in the spirit of the 1m/5m/15m
+/// load averages: short windows react to a change quickly, long ones reflect sustained behavior.
+#[derive(Debug)]
+pub struct MultiWindowDetector {
+    windows: Vec<Detector>,
+}
+
+impl MultiWindowDetector {
+    /// New MultiWindowDetector with one window per entry of window_lengths, all using the default config.
+    pub fn new(window_lengths: &[u32]) -> Self {
+        MultiWindowDetector::from_config(DetectorConfig::default(), window_lengths)
+    }
+
+    /// New MultiWindowDetector whose windows share config except for their window_length.
+    pub fn from_config(config: DetectorConfig, window_lengths: &[u32]) -> Self {
+        let windows = window_lengths.iter()
+            .map(|window_length| Detector::from_config(DetectorConfig { window_length: *window_length, ..config.clone() }))
+            .collect();
+        MultiWindowDetector { windows }
+    }
+
+    /// Number of windows.
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Whether there are no windows.
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Detector of window idx.
+    pub fn window(&self, idx: usize) -> Option<&Detector> {
+        self.windows.get(idx)
+    }
+
+    /// Insertion of heartbeat arrival time into every window.
+    pub async fn insert(&self, arrived_at: DateTime<Local>) -> Result<(), Box<dyn Error>> {
+        for window in &self.windows {
+            window.insert(arrived_at).await?;
+        }
+        Ok(())
+    }
+
+    /// Phi at t computed over window idx.
+    pub async fn phi_for_window(&self, idx: usize, t: DateTime<Local>) -> Result<f64, Box<dyn Error>> {
+        let window = self.windows.get(idx).ok_or(PhiError::InvalidWindowIndex(idx))?;
+        window.phi(t).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Add;
+    use chrono::{Duration, Local, TimeDelta};
+    use crate::{DetectorConfig, MultiWindowDetector, PhiError};
+
+    #[tokio::test]
+    async fn test_short_window_reacts_faster() {
+        let config = DetectorConfig { min_std_deviation: TimeDelta::milliseconds(20), ..Default::default() };
+        let detector = MultiWindowDetector::from_config(config, &[5, 100]);
+        let mut arrived_at = Local::now();
+        detector.insert(arrived_at).await.unwrap();
+        for i in 0..100 {
+            arrived_at = arrived_at.add(Duration::milliseconds(100 + i % 3 * 10));
+            detector.insert(arrived_at).await.unwrap();
+        }
+        // the sender slows down
+        for i in 0..5 {
+            arrived_at = arrived_at.add(Duration::milliseconds(300 + i % 3 * 10));
+            detector.insert(arrived_at).await.unwrap();
+        }
+        let t = arrived_at.add(Duration::milliseconds(250));
+        let short = detector.phi_for_window(0, t).await.unwrap();
+        let long = detector.phi_for_window(1, t).await.unwrap();
+        assert!(short < 1., "short {short}");
+        assert!(long > 2., "long {long}");
+
+        let err = detector.phi_for_window(2, t).await.unwrap_err();
+        assert_eq!(Some(&PhiError::InvalidWindowIndex(2)), err.downcast_ref::<PhiError>());
+    }
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+use std::net::SocketAddr;
+
+/// Node identifier usable as a `DetectorRegistry` key, made from a socket address or a name.
+/// Displayed, and serialized with the `serde` feature, as the address or the name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(Repr);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Repr {
+    Addr(SocketAddr),
+    Name(String),
+}
+
+impl NodeId {
+    /// Socket address of the node, None if it was identified by name.
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        match self.0 {
+            Repr::Addr(addr) => Some(addr),
+            Repr::Name(_) => None,
+        }
+    }
+}
+
+impl From<SocketAddr> for NodeId {
+    fn from(addr: SocketAddr) -> Self {
+        NodeId(Repr::Addr(addr))
+    }
+}
+
+impl From<String> for NodeId {
+    fn from(name: String) -> Self {
+        NodeId(Repr::Name(name))
+    }
+}
+
+impl From<&str> for NodeId {
+    fn from(name: &str) -> Self {
+        NodeId(Repr::Name(name.to_string()))
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Repr::Addr(addr) => addr.fmt(f),
+            Repr::Name(name) => name.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for NodeId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use chrono::Local;
+    use crate::{DetectorConfig, DetectorRegistry, NodeId};
+
+    #[tokio::test]
+    async fn test_socket_addr_as_registry_key() {
+        let registry: DetectorRegistry = DetectorRegistry::new(DetectorConfig::default());
+        let addr: SocketAddr = "10.0.0.1:7946".parse().unwrap();
+        let detector = registry.get_or_create(NodeId::from(addr)).await;
+        registry.heartbeat(addr.into(), Local::now()).await.unwrap();
+        assert!(Arc::ptr_eq(&detector, &registry.get(&NodeId::from(addr)).await.unwrap()));
+        assert!(registry.get(&NodeId::from("10.0.0.1:7946")).await.is_none());
+        assert_eq!("10.0.0.1:7946", NodeId::from(addr).to_string());
+        assert_eq!(Some(addr), registry.keys().await[0].socket_addr());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_as_string() {
+        let addr: SocketAddr = "[::1]:80".parse().unwrap();
+        assert_eq!(serde_json::json!(["[::1]:80", "node-a"]), serde_json::to_value([NodeId::from(addr), NodeId::from("node-a")]).unwrap());
+    }
+}
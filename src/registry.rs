@@ -0,0 +1,1507 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex as SyncMutex, PoisonError, RwLock as SyncRwLock};
+use std::time::Duration as StdDuration;
+use chrono::{DateTime, Local, TimeDelta};
+use futures_util::stream::{self, Stream};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use crate::{Detector, DetectorConfig, Evaluation, NodeId, NodeStatus, NodeStore, PhiError, PhiInteraction, Severity, StalenessReport, StoreError, WriteBackPolicy};
+use crate::store::Persistence;
+
+/// Async callback invoked with a node key and its phi
+type Hook<K> = Arc<dyn Fn(K, f64) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// Registry of Detectors keyed by node identifier
+pub struct DetectorRegistry<K = NodeId> {
+    default_config: DetectorConfig,
+    overrides: RwLock<HashMap<K, DetectorConfig>>,
+    detectors: RwLock<HashMap<K, Arc<Detector>>>,
+    suspected: Mutex<HashSet<K>>,
+    on_suspect: SyncRwLock<Vec<Hook<K>>>,
+    on_recover: SyncRwLock<Vec<Hook<K>>>,
+    persistence: Option<Persistence<K>>,
+    shutdown: CancellationToken,
+    tasks: SyncMutex<JoinSet<()>>,
+    #[cfg(feature = "log")]
+    log_target: String,
+}
+
+/// Time `DetectorRegistry::shutdown` waits for the spawned tasks to finish.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: StdDuration = StdDuration::from_secs(5);
+
+/// Default target of the transition logs emitted by `DetectorRegistry::sweep`.
+#[cfg(feature = "log")]
+pub const DEFAULT_LOG_TARGET: &str = "phi_accrual_detector";
+
+/// Change of a node's suspicion observed by `DetectorRegistry::sweep`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transition {
+    /// Phi rose above the node's threshold.
+    Suspected(f64),
+    /// Phi of a suspected node fell back to or below the node's threshold.
+    Recovered(f64),
+}
+
+impl<K: fmt::Debug> fmt::Debug for DetectorRegistry<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DetectorRegistry")
+            .field("default_config", &self.default_config)
+            .field("overrides", &self.overrides)
+            .field("detectors", &self.detectors)
+            .field("suspected", &self.suspected)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K> DetectorRegistry<K>
+    where K: Eq + Hash + Clone + Send + Sync {
+    /// New empty registry creating detectors with default_config on first heartbeat.
+    pub fn new(default_config: DetectorConfig) -> Self {
+        DetectorRegistry {
+            default_config,
+            overrides: RwLock::new(HashMap::new()),
+            detectors: RwLock::new(HashMap::new()),
+            suspected: Mutex::new(HashSet::new()),
+            on_suspect: SyncRwLock::new(vec![]),
+            on_recover: SyncRwLock::new(vec![]),
+            persistence: None,
+            shutdown: CancellationToken::new(),
+            tasks: SyncMutex::new(JoinSet::new()),
+            #[cfg(feature = "log")]
+            log_target: DEFAULT_LOG_TARGET.to_string(),
+        }
+    }
+
+    /// Target of the transition logs emitted by `sweep`, so operators can filter them.
+    #[cfg(feature = "log")]
+    pub fn with_log_target(mut self, log_target: impl Into<String>) -> Self {
+        self.log_target = log_target.into();
+        self
+    }
+
+    /// Persist node snapshots in store, written back according to policy. Without a store the
+    /// registry only keeps its detectors in memory.
+    pub fn with_store(mut self, store: Arc<dyn NodeStore<K>>, policy: WriteBackPolicy) -> Self {
+        self.persistence = Some(Persistence::new(store, policy));
+        self
+    }
+
+    /// New registry with pre-created detectors built from per-node configs, which are kept as
+    /// the overrides of their keys.
+    pub fn from_configs(configs: HashMap<K, DetectorConfig>) -> Self {
+        let detectors = configs.iter()
+            .map(|(key, config)| (key.clone(), Arc::new(Detector::from_config(config.clone()))))
+            .collect();
+        DetectorRegistry {
+            overrides: RwLock::new(configs),
+            detectors: RwLock::new(detectors),
+            ..DetectorRegistry::new(DetectorConfig::default())
+        }
+    }
+
+    /// Register a hook invoked by `sweep` when a node becomes suspected.
+    pub fn on_suspect<F, Fut>(&self, hook: F)
+        where F: Fn(K, f64) -> Fut + Send + Sync + 'static,
+              Fut: Future<Output = ()> + Send + 'static {
+        let hook: Hook<K> = Arc::new(move |key, phi| Box::pin(hook(key, phi)));
+        self.on_suspect.write().unwrap_or_else(PoisonError::into_inner).push(hook);
+    }
+
+    /// Register a hook invoked by `sweep` when a suspected node recovers.
+    pub fn on_recover<F, Fut>(&self, hook: F)
+        where F: Fn(K, f64) -> Fut + Send + Sync + 'static,
+              Fut: Future<Output = ()> + Send + 'static {
+        let hook: Hook<K> = Arc::new(move |key, phi| Box::pin(hook(key, phi)));
+        self.on_recover.write().unwrap_or_else(PoisonError::into_inner).push(hook);
+    }
+
+    /// Detector registered for key, if any.
+    pub async fn get(&self, key: &K) -> Option<Arc<Detector>> {
+        self.detectors.read().await.get(key).cloned()
+    }
+
+    /// Detector registered for key, creating it with the key's config if absent.
+    pub async fn get_or_create(&self, key: K) -> Arc<Detector> {
+        if let Some(detector) = self.get(&key).await {
+            return detector;
+        }
+        let mut detectors = self.detectors.write().await;
+        if let Some(detector) = detectors.get(&key) {
+            return Arc::clone(detector);
+        }
+        // read under the map lock, so that set_config cannot slip in between
+        let config = self.config_for(&key).await;
+        let detector = Arc::new(Detector::from_config(config));
+        detectors.insert(key, Arc::clone(&detector));
+        detector
+    }
+
+    /// Config override of key, or the default config when it has none.
+    pub async fn config_for(&self, key: &K) -> DetectorConfig {
+        self.overrides.read().await.get(key).cloned().unwrap_or_else(|| self.default_config.clone())
+    }
+
+    /// Override the config of key. A live detector is reconfigured right away, otherwise the
+    /// config is used once the detector is created.
+    pub async fn set_config(&self, key: K, config: DetectorConfig) -> Result<(), PhiError> {
+        config.validate()?;
+        let detectors = self.detectors.write().await;
+        if let Some(detector) = detectors.get(&key) {
+            detector.apply_config(config.clone()).await?;
+        }
+        self.overrides.write().await.insert(key, config);
+        Ok(())
+    }
+
+    /// Record a heartbeat for key, writing it back to the store as the write-back policy demands.
+    pub async fn heartbeat(&self, key: K, arrived_at: DateTime<Local>) -> Result<(), Box<dyn Error>> {
+        let Some(persistence) = &self.persistence else {
+            return self.get_or_create(key).await.insert(arrived_at).await;
+        };
+        let detector = self.get_or_create(key.clone()).await;
+        detector.insert(arrived_at).await?;
+        match persistence.policy {
+            WriteBackPolicy::OnChange => self.write_back(persistence, &key, &detector).await?,
+            WriteBackPolicy::Periodic(interval) => {
+                persistence.dirty.lock().await.insert(key);
+                let mut last_flush = persistence.last_flush.lock().await;
+                if last_flush.map_or(true, |at| arrived_at - at >= interval) {
+                    *last_flush = Some(arrived_at);
+                    drop(last_flush);
+                    self.flush().await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write every node changed since its last write-back to the store, returning how many were
+    /// written. Stops at the first failure, e.g. a version conflict, and returns it, leaving
+    /// that node and the ones not yet written pending.
+    pub async fn flush(&self) -> Result<usize, StoreError> {
+        let Some(persistence) = &self.persistence else {
+            return Ok(0);
+        };
+        let pending: Vec<K> = persistence.dirty.lock().await.drain().collect();
+        let mut written = 0;
+        for (i, key) in pending.iter().enumerate() {
+            let Some(detector) = self.get(key).await else {
+                continue;
+            };
+            if let Err(e) = self.write_back(persistence, key, &detector).await {
+                persistence.dirty.lock().await.extend(pending[i..].iter().cloned());
+                return Err(e);
+            }
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Spawn a task flushing pending snapshots every interval until token or the registry is
+    /// cancelled, flushing one last time before it exits. Failed flushes are retried on the next tick.
+    pub fn spawn_flusher(self: &Arc<Self>, interval: StdDuration, token: CancellationToken)
+        where K: 'static {
+        let registry = Arc::clone(self);
+        let shutdown = self.shutdown.clone();
+        self.tasks.lock().unwrap_or_else(PoisonError::into_inner).spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = shutdown.cancelled() => break,
+                    _ = ticker.tick() => {
+                        let _ = registry.flush().await;
+                    }
+                }
+            }
+            let _ = registry.flush().await;
+        });
+    }
+
+    /// Cancel every task spawned by the registry and wait up to DEFAULT_SHUTDOWN_TIMEOUT for them
+    /// to finish. Returns whether all of them finished in time.
+    pub async fn shutdown(&self) -> bool {
+        self.shutdown_timeout(DEFAULT_SHUTDOWN_TIMEOUT).await
+    }
+
+    /// Same as `shutdown` with an explicit timeout. Tasks still running after it are aborted.
+    pub async fn shutdown_timeout(&self, timeout: StdDuration) -> bool {
+        self.shutdown.cancel();
+        let mut tasks = std::mem::take(&mut *self.tasks.lock().unwrap_or_else(PoisonError::into_inner));
+        let finished = tokio::time::timeout(timeout, async {
+            while tasks.join_next().await.is_some() {}
+        }).await.is_ok();
+        tasks.abort_all();
+        finished
+    }
+
+    /// Replace the in-memory detectors by the snapshots held in the store, returning how many
+    /// were loaded. Used to resume after a restart or to catch up with other replicas.
+    pub async fn reload(&self) -> Result<usize, StoreError> {
+        let Some(persistence) = &self.persistence else {
+            return Ok(0);
+        };
+        let mut loaded = 0;
+        for key in persistence.store.list().await? {
+            let Some(versioned) = persistence.store.get(&key).await? else {
+                continue;
+            };
+            persistence.versions.lock().await.insert(key.clone(), versioned.version);
+            persistence.dirty.lock().await.remove(&key);
+            self.detectors.write().await.insert(key, Arc::new(versioned.value.into_detector()));
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+
+    /// Store the snapshot of key's detector, expecting the version last seen by this registry.
+    async fn write_back(&self, persistence: &Persistence<K>, key: &K, detector: &Detector) -> Result<(), StoreError> {
+        let expected = persistence.versions.lock().await.get(key).copied().unwrap_or(0);
+        let snapshot = detector.snapshot().await.map_err(|_| StoreError::Poisoned)?;
+        let version = persistence.store.put(key.clone(), snapshot, expected).await?;
+        persistence.versions.lock().await.insert(key.clone(), version);
+        Ok(())
+    }
+
+    /// Phi of key at t, None for unknown keys.
+    pub async fn phi(&self, key: &K, t: DateTime<Local>) -> Result<Option<f64>, Box<dyn Error>> {
+        match self.get(key).await {
+            Some(detector) => Ok(Some(detector.phi(t).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Keys whose phi at t exceeds their configured threshold.
+    pub async fn suspected(&self, t: DateTime<Local>) -> Result<Vec<K>, Box<dyn Error>> {
+        let mut suspected = vec![];
+        for (key, detector) in self.snapshot().await {
+            if detector.phi(t).await? > detector.config()?.threshold {
+                suspected.push(key);
+            }
+        }
+        Ok(suspected)
+    }
+
+    /// Resolve with phi and the time it happened once the phi of key exceeds its configured
+    /// threshold, see `Detector::wait_until_suspected`. None for unknown keys.
+    pub async fn wait_until_suspected(&self, key: &K) -> Result<Option<(f64, DateTime<Local>)>, Box<dyn Error>> {
+        let Some(detector) = self.get(key).await else {
+            return Ok(None);
+        };
+        let threshold = detector.config()?.threshold;
+        Ok(Some(detector.wait_until_suspected(threshold).await?))
+    }
+
+    /// Keys whose phi at t exceeds their configured threshold, with their severity band.
+    pub async fn suspected_with_severity(&self, t: DateTime<Local>) -> Result<Vec<(K, Severity)>, Box<dyn Error>> {
+        let mut suspected = vec![];
+        for (key, detector) in self.snapshot().await {
+            let config = detector.config()?;
+            let phi = detector.phi(t).await?;
+            if phi > config.threshold {
+                suspected.push((key, config.severity_bands.classify(phi)));
+            }
+        }
+        Ok(suspected)
+    }
+
+    /// Warm-start to_key (created with the default config if absent) from from_key's statistics.
+    /// Returns None when from_key is unknown.
+    pub async fn clone_node(&self, from_key: &K, to_key: K) -> Result<Option<Arc<Detector>>, Box<dyn Error>> {
+        let Some(source) = self.get(from_key).await else {
+            return Ok(None);
+        };
+        let target = self.get_or_create(to_key).await;
+        target.warm_start_from(&source).await?;
+        Ok(Some(target))
+    }
+
+    /// Apply config to every registered detector, returning how many were updated.
+    pub async fn apply_config_all(&self, config: DetectorConfig) -> Result<usize, PhiError> {
+        self.apply_config_where(config, |_| true).await
+    }
+
+    /// Apply config to the registered detectors whose key matches filter, returning how many were updated.
+    /// The config is validated once up front, so either every matching detector is updated or none is.
+    pub async fn apply_config_where<F>(&self, config: DetectorConfig, filter: F) -> Result<usize, PhiError>
+        where F: Fn(&K) -> bool {
+        config.validate()?;
+        let mut applied = 0;
+        for (key, detector) in self.snapshot().await {
+            if filter(&key) {
+                detector.apply_config(config.clone()).await?;
+                applied += 1;
+            }
+        }
+        Ok(applied)
+    }
+
+    /// Remove the detector registered for key, running its shutdown callbacks. Its snapshot
+    /// stays in the store, if any.
+    pub async fn remove(&self, key: &K) -> Option<Arc<Detector>> {
+        let detector = self.remove_locked(&mut *self.detectors.write().await, key).await?;
+        detector.finish().await;
+        Some(detector)
+    }
+
+    /// Remove key from detectors, the locked map, along with its suspicion and persistence
+    /// state. The caller runs the shutdown callbacks once it released the lock.
+    async fn remove_locked(&self, detectors: &mut HashMap<K, Arc<Detector>>, key: &K) -> Option<Arc<Detector>> {
+        self.suspected.lock().await.remove(key);
+        if let Some(persistence) = &self.persistence {
+            persistence.versions.lock().await.remove(key);
+            persistence.dirty.lock().await.remove(key);
+        }
+        detectors.remove(key)
+    }
+
+    /// Remove every detector whose last heartbeat, or creation without any, is more than
+    /// older_than ago, returning the evicted keys.
+    pub async fn evict_idle(&self, older_than: TimeDelta) -> Result<Vec<K>, Box<dyn Error>> {
+        let cutoff = Local::now() - older_than;
+        let mut evicted = vec![];
+        for (key, detector) in self.snapshot().await {
+            if detector.last_arrived_at().await? >= cutoff {
+                continue;
+            }
+            let mut detectors = self.detectors.write().await;
+            // checked again under the map lock, so that a heartbeat since is not thrown away
+            let Some(current) = detectors.get(&key) else {
+                continue;
+            };
+            if current.last_arrived_at().await? >= cutoff {
+                continue;
+            }
+            let removed = self.remove_locked(&mut detectors, &key).await;
+            drop(detectors);
+            if let Some(removed) = removed {
+                removed.finish().await;
+                evicted.push(key);
+            }
+        }
+        Ok(evicted)
+    }
+
+    /// Registered keys.
+    pub async fn keys(&self) -> Vec<K> {
+        self.detectors.read().await.keys().cloned().collect()
+    }
+
+    /// Number of registered detectors.
+    pub async fn len(&self) -> usize {
+        self.detectors.read().await.len()
+    }
+
+    /// Whether the registry holds no detectors.
+    pub async fn is_empty(&self) -> bool {
+        self.detectors.read().await.is_empty()
+    }
+
+    /// Staleness report of every registered node at now.
+    pub async fn staleness_all(&self, now: DateTime<Local>) -> Result<Vec<(K, StalenessReport)>, Box<dyn Error>> {
+        let mut reports = vec![];
+        for (key, detector) in self.snapshot().await {
+            reports.push((key, detector.staleness(now).await?));
+        }
+        Ok(reports)
+    }
+
+    /// Severity band of every registered node at t.
+    pub async fn severity_all(&self, t: DateTime<Local>) -> Result<Vec<(K, Severity)>, Box<dyn Error>> {
+        let mut severities = vec![];
+        for (key, detector) in self.snapshot().await {
+            severities.push((key, detector.severity(t).await?));
+        }
+        Ok(severities)
+    }
+
+    /// Stream of the evaluation of every registered node at now against threshold, each computed
+    /// only once the stream is polled for it. The registered nodes are taken on the first poll,
+    /// nodes registered afterwards are not included and nodes whose evaluation fails are skipped.
+    pub fn evaluate_all(self: Arc<Self>, now: DateTime<Local>, threshold: f64) -> impl Stream<Item = (K, Evaluation)> {
+        stream::unfold(None, move |nodes: Option<std::vec::IntoIter<(K, Arc<Detector>)>>| {
+            let registry = Arc::clone(&self);
+            async move {
+                let mut nodes = match nodes {
+                    Some(nodes) => nodes,
+                    None => registry.snapshot().await.into_iter(),
+                };
+                while let Some((key, detector)) = nodes.next() {
+                    if let Ok(evaluation) = detector.evaluate(now, threshold).await {
+                        return Some(((key, evaluation), Some(nodes)));
+                    }
+                }
+                None
+            }
+        })
+    }
+
+    /// Status of every registered node at t, evaluated concurrently.
+    pub async fn phi_all(&self, t: DateTime<Local>) -> Result<Vec<(K, NodeStatus)>, Box<dyn Error>>
+        where K: 'static {
+        let mut tasks = JoinSet::new();
+        for (key, detector) in self.snapshot().await {
+            tasks.spawn(async move {
+                let status = detector.status(t).await.map_err(|e| e.to_string());
+                (key, status)
+            });
+        }
+        let mut statuses = Vec::with_capacity(tasks.len());
+        while let Some(joined) = tasks.join_next().await {
+            let (key, status) = joined?;
+            statuses.push((key, status?));
+        }
+        Ok(statuses)
+    }
+
+    /// Evaluate every node at t and report the ones whose suspicion changed since the previous sweep.
+    /// Registered hooks run after all internal locks are released, sequentially for a node and
+    /// concurrently across nodes. A panicking hook is isolated and does not fail the sweep.
+    pub async fn sweep(&self, t: DateTime<Local>) -> Result<Vec<(K, Transition)>, Box<dyn Error>>
+        where K: fmt::Debug + 'static {
+        let statuses = self.phi_all(t).await?;
+        Ok(self.apply_statuses(statuses, t).await)
+    }
+
+    /// Record the suspicion changes among statuses evaluated at t and run the hooks for them.
+    #[cfg_attr(not(feature = "log"), allow(unused_variables))]
+    async fn apply_statuses(&self, statuses: Vec<(K, NodeStatus)>, t: DateTime<Local>) -> Vec<(K, Transition)>
+        where K: fmt::Debug + 'static {
+        let mut transitions = vec![];
+        {
+            let mut suspected = self.suspected.lock().await;
+            for (key, status) in statuses {
+                match status {
+                    NodeStatus::Suspected(phi) if suspected.insert(key.clone()) => {
+                        transitions.push((key, Transition::Suspected(phi)));
+                    }
+                    NodeStatus::Healthy(phi) | NodeStatus::Probation(phi) if suspected.remove(&key) => {
+                        transitions.push((key, Transition::Recovered(phi)));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let on_suspect = self.on_suspect.read().unwrap_or_else(PoisonError::into_inner).clone();
+        let on_recover = self.on_recover.read().unwrap_or_else(PoisonError::into_inner).clone();
+        let mut tasks = JoinSet::new();
+        for (key, transition) in transitions.iter().cloned() {
+            let (hooks, phi) = match transition {
+                Transition::Suspected(phi) => (on_suspect.clone(), phi),
+                Transition::Recovered(phi) => (on_recover.clone(), phi),
+            };
+            if hooks.is_empty() {
+                continue;
+            }
+            tasks.spawn(async move {
+                for hook in hooks {
+                    // each hook runs in its own task so a panic only loses that hook
+                    let _ = tokio::spawn(hook(key.clone(), phi)).await;
+                }
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+        #[cfg(feature = "log")]
+        self.log_transitions(&transitions, t).await;
+        transitions
+    }
+
+    /// Spawn a scheduler evaluating the nodes selected by options every interval until token or
+    /// the registry is cancelled. Each tick splits the nodes across at most options.workers tasks
+    /// and feeds the resulting transitions to the same hooks as `sweep`.
+    pub fn spawn_scheduler(self: &Arc<Self>, options: SchedulerOptions<K>, token: CancellationToken) -> SchedulerHandle
+        where K: fmt::Debug + 'static {
+        let registry = Arc::clone(self);
+        let shutdown = self.shutdown.clone();
+        let stats = Arc::new(SyncMutex::new(TickStats::default()));
+        let handle = SchedulerHandle { stats: Arc::clone(&stats) };
+        self.tasks.lock().unwrap_or_else(PoisonError::into_inner).spawn(async move {
+            let mut ticker = tokio::time::interval(options.interval);
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = shutdown.cancelled() => break,
+                    _ = ticker.tick() => registry.scheduled_tick(&options, &stats).await,
+                }
+            }
+        });
+        handle
+    }
+
+    /// One tick of a scheduler: evaluate the selected nodes in batches and apply the transitions.
+    async fn scheduled_tick(&self, options: &SchedulerOptions<K>, stats: &SyncMutex<TickStats>)
+        where K: fmt::Debug + 'static {
+        let started = tokio::time::Instant::now();
+        let t = Local::now();
+        let mut nodes = self.snapshot().await;
+        if let Some(filter) = &options.filter {
+            nodes.retain(|(key, _)| filter(key));
+        }
+        let evaluated = nodes.len();
+        let workers_count = options.workers.max(1);
+        let batch_size = ((evaluated + workers_count - 1) / workers_count).max(1);
+        let mut workers = JoinSet::new();
+        while !nodes.is_empty() {
+            let batch: Vec<_> = nodes.drain(..batch_size.min(nodes.len())).collect();
+            workers.spawn(async move {
+                let mut statuses = Vec::with_capacity(batch.len());
+                for (key, detector) in batch {
+                    if let Ok(status) = detector.status(t).await.map_err(|e| e.to_string()) {
+                        statuses.push((key, status));
+                    }
+                }
+                statuses
+            });
+        }
+        let tasks = workers.len();
+        let mut statuses = Vec::with_capacity(evaluated);
+        while let Some(joined) = workers.join_next().await {
+            statuses.extend(joined.unwrap_or_default());
+        }
+        self.apply_statuses(statuses, t).await;
+
+        let duration = started.elapsed();
+        let mut stats = stats.lock().unwrap_or_else(PoisonError::into_inner);
+        stats.ticks += 1;
+        stats.evaluated = evaluated;
+        stats.total_evaluated += evaluated as u64;
+        stats.tasks = tasks;
+        stats.last_duration = duration;
+        stats.max_duration = stats.max_duration.max(duration);
+        if duration > options.interval {
+            stats.overruns += 1;
+        }
+    }
+
+    /// Emit a structured log record for every transition, warn when suspected and info when recovered.
+    #[cfg(feature = "log")]
+    async fn log_transitions(&self, transitions: &[(K, Transition)], t: DateTime<Local>)
+        where K: fmt::Debug {
+        for (key, transition) in transitions {
+            let Some(detector) = self.get(key).await else {
+                continue;
+            };
+            let Ok(config) = detector.config() else {
+                continue;
+            };
+            let elapsed_ms = (t - detector.last_arrived_at().await.unwrap_or(t)).num_milliseconds();
+            let threshold = config.threshold;
+            let target = self.log_target.as_str();
+            match *transition {
+                Transition::Suspected(phi) => log::warn!(target: target,
+                    node:? = key, phi = phi, elapsed_ms = elapsed_ms, threshold = threshold;
+                    "node {:?} suspected with phi {:.3} after {}ms", key, phi, elapsed_ms),
+                Transition::Recovered(phi) => log::info!(target: target,
+                    node:? = key, phi = phi, elapsed_ms = elapsed_ms, threshold = threshold;
+                    "node {:?} recovered with phi {:.3}", key, phi),
+            }
+        }
+    }
+
+    /// Health score of the registry at t, computed from a single `phi_all` sweep.
+    pub async fn health_score(&self, t: DateTime<Local>, opts: &HealthScoreOptions<K>) -> Result<HealthScore, Box<dyn Error>>
+        where K: 'static {
+        let mut health = HealthScore { score: 100., healthy: 0, probation: 0, warming: 0, suspected: 0, idle: 0 };
+        let (mut healthy_weight, mut total_weight) = (0., 0.);
+        for (key, status) in self.phi_all(t).await? {
+            let weight = opts.weights.get(&key).copied().unwrap_or(1.);
+            match status {
+                NodeStatus::Idle => health.idle += 1,
+                NodeStatus::Warming(_) => {
+                    health.warming += 1;
+                    if opts.warming_as_healthy {
+                        healthy_weight += weight;
+                        total_weight += weight;
+                    }
+                }
+                NodeStatus::Healthy(_) => {
+                    health.healthy += 1;
+                    healthy_weight += weight;
+                    total_weight += weight;
+                }
+                NodeStatus::Probation(_) => {
+                    health.probation += 1;
+                    healthy_weight += weight;
+                    total_weight += weight;
+                }
+                NodeStatus::Suspected(_) => {
+                    health.suspected += 1;
+                    total_weight += weight;
+                }
+            }
+        }
+        if total_weight > 0. {
+            health.score = 100. * healthy_weight / total_weight;
+        }
+        Ok(health)
+    }
+
+    /// Candidates ordered by ascending phi at t, see `rank_with` with `UnrankedPolicy::Last`.
+    pub async fn rank(&self, candidates: &[K], t: DateTime<Local>) -> Result<Vec<(K, f64)>, Box<dyn Error>> {
+        self.rank_with(candidates, t, UnrankedPolicy::Last).await
+    }
+
+    /// Candidates ordered by ascending phi at t. Equal phis keep their order in candidates.
+    /// Warming-up nodes keep their phi, idle and unknown nodes are reported with infinite phi.
+    /// A phi already computed for t, e.g. by a sweep, is reused if the node has not changed since.
+    pub async fn rank_with(&self, candidates: &[K], t: DateTime<Local>, policy: UnrankedPolicy) -> Result<Vec<(K, f64)>, Box<dyn Error>> {
+        let mut ranked = vec![];
+        let mut unranked = vec![];
+        for key in candidates {
+            let status = match self.get(key).await {
+                Some(detector) => detector.status(t).await?,
+                None => NodeStatus::Idle,
+            };
+            match status {
+                NodeStatus::Healthy(phi) | NodeStatus::Suspected(phi) | NodeStatus::Probation(phi) => ranked.push((key.clone(), phi)),
+                NodeStatus::Warming(phi) => unranked.push((key.clone(), phi)),
+                NodeStatus::Idle => unranked.push((key.clone(), f64::INFINITY)),
+            }
+        }
+        ranked.sort_by(|a, b| a.1.total_cmp(&b.1));
+        unranked.sort_by(|a, b| a.1.total_cmp(&b.1));
+        if policy == UnrankedPolicy::Last {
+            ranked.extend(unranked);
+        }
+        Ok(ranked)
+    }
+
+    /// Healthiest of candidates at t, preferring warmed-up nodes.
+    pub async fn best(&self, candidates: &[K], t: DateTime<Local>) -> Result<Option<K>, Box<dyn Error>> {
+        Ok(self.rank(candidates, t).await?.into_iter().next().map(|(key, _)| key))
+    }
+
+    /// Copy of the registered (key, detector) pairs, so callers can evaluate without holding the map lock.
+    async fn snapshot(&self) -> Vec<(K, Arc<Detector>)> {
+        self.detectors.read().await.iter()
+            .map(|(key, detector)| (key.clone(), Arc::clone(detector)))
+            .collect()
+    }
+}
+
+/// Predicate selecting the nodes a scheduler evaluates
+pub type NodeFilter<K> = Arc<dyn Fn(&K) -> bool + Send + Sync>;
+
+/// Options of `DetectorRegistry::spawn_scheduler`
+#[derive(Clone)]
+pub struct SchedulerOptions<K> {
+    /// Shared cadence at which the nodes are evaluated.
+    pub interval: StdDuration,
+    /// Maximum number of tasks evaluating a single tick.
+    pub workers: usize,
+    /// Nodes to evaluate, all of them when None.
+    pub filter: Option<NodeFilter<K>>,
+}
+
+impl<K> Default for SchedulerOptions<K> {
+    fn default() -> Self {
+        SchedulerOptions {
+            interval: StdDuration::from_secs(1),
+            workers: 4,
+            filter: None,
+        }
+    }
+}
+
+impl<K> fmt::Debug for SchedulerOptions<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SchedulerOptions")
+            .field("interval", &self.interval)
+            .field("workers", &self.workers)
+            .field("filtered", &self.filter.is_some())
+            .finish()
+    }
+}
+
+/// Timing of the ticks run by a scheduler, so operators can tell when it falls behind
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TickStats {
+    pub ticks: u64,
+    /// Nodes evaluated by the last tick.
+    pub evaluated: usize,
+    /// Nodes evaluated across all ticks.
+    pub total_evaluated: u64,
+    /// Tasks spawned by the last tick.
+    pub tasks: usize,
+    pub last_duration: StdDuration,
+    pub max_duration: StdDuration,
+    /// Ticks that took longer than the interval.
+    pub overruns: u64,
+}
+
+/// Handle of a scheduler spawned by `DetectorRegistry::spawn_scheduler`
+#[derive(Clone, Debug)]
+pub struct SchedulerHandle {
+    stats: Arc<SyncMutex<TickStats>>,
+}
+
+impl SchedulerHandle {
+    /// Timing of the ticks run so far.
+    pub fn stats(&self) -> TickStats {
+        *self.stats.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Options for `DetectorRegistry::health_score`
+#[derive(Clone, Debug)]
+pub struct HealthScoreOptions<K> {
+    /// Per-node weights, nodes without an entry weigh 1.
+    pub weights: HashMap<K, f64>,
+    /// Whether warming-up nodes count as healthy. Otherwise they are left out of the score.
+    pub warming_as_healthy: bool,
+}
+
+impl<K> Default for HealthScoreOptions<K> {
+    fn default() -> Self {
+        HealthScoreOptions {
+            weights: HashMap::new(),
+            warming_as_healthy: true,
+        }
+    }
+}
+
+/// Placement of warming-up, idle and unknown candidates in `DetectorRegistry::rank_with`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnrankedPolicy {
+    /// Rank them after every warmed-up candidate.
+    #[default]
+    Last,
+    /// Leave them out of the ranking.
+    Exclude,
+}
+
+/// Aggregated health of all nodes in a registry
+#[derive(Clone, Debug, PartialEq)]
+pub struct HealthScore {
+    /// Weighted percentage (0-100) of scored nodes that are not suspected. 100 when no node is scored.
+    pub score: f64,
+    pub healthy: usize,
+    /// Nodes serving probation after recovering, scored as not suspected.
+    pub probation: usize,
+    pub warming: usize,
+    pub suspected: usize,
+    /// Nodes that never sent a heartbeat, these are never scored.
+    pub idle: usize,
+}
+
+/// Registry of isolated namespaces, each holding its own DetectorRegistry.
+/// Nodes are keyed by (namespace, node), so keys never collide across namespaces.
+#[derive(Debug)]
+pub struct NamespacedRegistry<N, K> {
+    default_config: DetectorConfig,
+    namespaces: RwLock<HashMap<N, Arc<DetectorRegistry<K>>>>,
+}
+
+/// Handle scoping registry operations to a single namespace
+#[derive(Debug, Clone)]
+pub struct NamespaceHandle<K> {
+    registry: Arc<DetectorRegistry<K>>,
+}
+
+impl<N, K> NamespacedRegistry<N, K>
+    where N: Eq + Hash + Clone + Send + Sync,
+          K: Eq + Hash + Clone + Send + Sync {
+    /// New empty registry whose namespaces default to default_config.
+    pub fn new(default_config: DetectorConfig) -> Self {
+        NamespacedRegistry {
+            default_config,
+            namespaces: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Handle for ns, creating the namespace with the registry default config if absent.
+    pub async fn namespace(&self, ns: N) -> NamespaceHandle<K> {
+        if let Some(registry) = self.namespaces.read().await.get(&ns) {
+            return NamespaceHandle { registry: Arc::clone(registry) };
+        }
+        let mut namespaces = self.namespaces.write().await;
+        let registry = namespaces.entry(ns)
+            .or_insert_with(|| Arc::new(DetectorRegistry::new(self.default_config.clone())));
+        NamespaceHandle { registry: Arc::clone(registry) }
+    }
+
+    /// Create (or replace) namespace ns whose detectors default to default_config.
+    pub async fn create_namespace(&self, ns: N, default_config: DetectorConfig) -> NamespaceHandle<K> {
+        let registry = Arc::new(DetectorRegistry::new(default_config));
+        self.namespaces.write().await.insert(ns, Arc::clone(&registry));
+        NamespaceHandle { registry }
+    }
+
+    /// Register registry, e.g. one built with a store, as namespace ns, replacing any before.
+    pub async fn insert_namespace(&self, ns: N, registry: DetectorRegistry<K>) -> NamespaceHandle<K> {
+        let registry = Arc::new(registry);
+        self.namespaces.write().await.insert(ns, Arc::clone(&registry));
+        NamespaceHandle { registry }
+    }
+
+    /// Drop namespace ns together with all of its detectors, first shutting down the tasks
+    /// spawned on its registry and running the detectors' shutdown callbacks. Returns whether it existed.
+    pub async fn drop_namespace(&self, ns: &N) -> bool {
+        let mut namespaces = self.namespaces.write().await;
+        let Some(registry) = namespaces.get(ns) else {
+            return false;
+        };
+        registry.shutdown().await;
+        for key in registry.keys().await {
+            registry.remove(&key).await;
+        }
+        namespaces.remove(ns);
+        true
+    }
+
+    /// Registered namespaces.
+    pub async fn namespaces(&self) -> Vec<N> {
+        self.namespaces.read().await.keys().cloned().collect()
+    }
+}
+
+impl<K> NamespaceHandle<K>
+    where K: Eq + Hash + Clone + Send + Sync {
+    /// Detector registered for key in this namespace, if any.
+    pub async fn get(&self, key: &K) -> Option<Arc<Detector>> {
+        self.registry.get(key).await
+    }
+
+    /// Record a heartbeat for key in this namespace.
+    pub async fn heartbeat(&self, key: K, arrived_at: DateTime<Local>) -> Result<(), Box<dyn Error>> {
+        self.registry.heartbeat(key, arrived_at).await
+    }
+
+    /// Phi of key in this namespace at t, None for unknown keys.
+    pub async fn phi(&self, key: &K, t: DateTime<Local>) -> Result<Option<f64>, Box<dyn Error>> {
+        self.registry.phi(key, t).await
+    }
+
+    /// Keys in this namespace whose phi at t exceeds their configured threshold.
+    pub async fn suspected(&self, t: DateTime<Local>) -> Result<Vec<K>, Box<dyn Error>> {
+        self.registry.suspected(t).await
+    }
+
+    /// Remove the detector registered for key in this namespace.
+    pub async fn remove(&self, key: &K) -> Option<Arc<Detector>> {
+        self.registry.remove(key).await
+    }
+
+    /// Validate config and use it for key in this namespace, see `DetectorRegistry::set_config`.
+    pub async fn set_config(&self, key: K, config: DetectorConfig) -> Result<(), PhiError> {
+        self.registry.set_config(key, config).await
+    }
+
+    /// Sweep this namespace at t, running its hooks, see `DetectorRegistry::sweep`.
+    pub async fn sweep(&self, t: DateTime<Local>) -> Result<Vec<(K, Transition)>, Box<dyn Error>>
+        where K: fmt::Debug + 'static {
+        self.registry.sweep(t).await
+    }
+
+    /// Registry backing this namespace, for hooks, schedulers and everything else.
+    pub fn registry(&self) -> &Arc<DetectorRegistry<K>> {
+        &self.registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::ops::{Add, Sub};
+    use chrono::{Duration, Local};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use chrono::TimeDelta;
+    use futures_util::StreamExt;
+    use tokio_util::sync::CancellationToken;
+    use crate::{DetectorConfig, DetectorRegistry, HealthScoreOptions, InMemoryStore, NodeStore, StoreError, WriteBackPolicy, NamespacedRegistry, PhiInteraction, SchedulerOptions, Severity, Transition, UnrankedPolicy};
+
+    #[tokio::test]
+    async fn test_from_configs_honors_individual_configs() {
+        let mut configs = HashMap::new();
+        configs.insert("db", DetectorConfig { window_length: 3, ..Default::default() });
+        configs.insert("batch", DetectorConfig { window_length: 5, threshold: 2.0, ..Default::default() });
+        let registry = DetectorRegistry::from_configs(configs);
+        assert_eq!(2, registry.len().await);
+
+        let mut curr_time = Local::now();
+        for _ in 0..10 {
+            registry.heartbeat("db", curr_time).await.unwrap();
+            registry.heartbeat("batch", curr_time).await.unwrap();
+            curr_time = curr_time.add(Duration::milliseconds(100));
+        }
+        let db = registry.get(&"db").await.unwrap();
+        let batch = registry.get(&"batch").await.unwrap();
+        assert_eq!(3, db.statistics.read().await.arrival_intervals.len());
+        assert_eq!(5, batch.statistics.read().await.arrival_intervals.len());
+        assert_eq!(2.0, batch.config().unwrap().threshold);
+        assert_eq!(crate::DEFAULT_THRESHOLD, db.config().unwrap().threshold);
+    }
+
+    #[tokio::test]
+    async fn test_config_overrides_diverge_for_same_gap() {
+        let registry = DetectorRegistry::new(DetectorConfig::default());
+        let quick = DetectorConfig { min_std_deviation: TimeDelta::milliseconds(20), ..Default::default() };
+        let patient = DetectorConfig { acceptable_pause: TimeDelta::seconds(30), ..quick.clone() };
+        registry.set_config("db", quick.clone()).await.unwrap();
+
+        let start = Local::now();
+        for at in [0, 500, 1010, 1500, 2000] {
+            registry.heartbeat("db", start.add(Duration::milliseconds(at))).await.unwrap();
+            registry.heartbeat("batch", start.add(Duration::milliseconds(at))).await.unwrap();
+        }
+        assert_eq!(quick, registry.get(&"db").await.unwrap().config().unwrap());
+        assert_eq!(DetectorConfig::default(), registry.get(&"batch").await.unwrap().config().unwrap());
+
+        registry.set_config("batch", patient.clone()).await.unwrap();
+        assert_eq!(patient, registry.get(&"batch").await.unwrap().config().unwrap());
+        assert_eq!(patient, registry.config_for(&"batch").await);
+
+        let t = start.add(Duration::milliseconds(2000 + 5000));
+        assert_eq!(vec!["db"], registry.suspected(t).await.unwrap());
+        assert!(registry.set_config("db", DetectorConfig { window_length: 0, ..quick }).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_set_config_reaches_detectors_created_concurrently() {
+        let registry = Arc::new(DetectorRegistry::new(DetectorConfig::default()));
+        let patient = DetectorConfig { acceptable_pause: TimeDelta::seconds(30), ..Default::default() };
+        let mut tasks = vec![];
+        for key in 0..200 {
+            let (creator, configurer) = (Arc::clone(&registry), Arc::clone(&registry));
+            tasks.push(tokio::spawn(async move { creator.get_or_create(key).await; }));
+            let patient = patient.clone();
+            tasks.push(tokio::spawn(async move { configurer.set_config(key, patient).await.unwrap(); }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        for key in 0..200 {
+            assert_eq!(patient, registry.get(&key).await.unwrap().config().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_back_policies() {
+        let store = Arc::new(InMemoryStore::new());
+        let eager = DetectorRegistry::new(DetectorConfig::default())
+            .with_store(store.clone(), WriteBackPolicy::OnChange);
+        let lazy = DetectorRegistry::new(DetectorConfig::default())
+            .with_store(store.clone(), WriteBackPolicy::Periodic(TimeDelta::seconds(1)));
+        let start = Local::now();
+        for at in [0, 100, 200] {
+            eager.heartbeat("eager", start.add(Duration::milliseconds(at))).await.unwrap();
+            lazy.heartbeat("lazy", start.add(Duration::milliseconds(at))).await.unwrap();
+        }
+        assert_eq!(3, store.get(&"eager").await.unwrap().unwrap().version);
+        let stored = store.get(&"lazy").await.unwrap().unwrap();
+        assert_eq!(1, stored.version);
+        assert!(stored.value.statistics.arrival_intervals.is_empty());
+
+        lazy.heartbeat("lazy", start.add(Duration::milliseconds(1000))).await.unwrap();
+        let stored = store.get(&"lazy").await.unwrap().unwrap();
+        assert_eq!(2, stored.version);
+        assert_eq!(vec![100, 100, 800], stored.value.statistics.arrival_intervals[..]);
+
+        lazy.heartbeat("lazy", start.add(Duration::milliseconds(1100))).await.unwrap();
+        assert_eq!(1, lazy.flush().await.unwrap());
+        assert_eq!(3, store.get(&"lazy").await.unwrap().unwrap().version);
+        assert_eq!(0, lazy.flush().await.unwrap());
+
+        // a node removed after it was marked pending is skipped, not counted as written
+        lazy.heartbeat("lazy", start.add(Duration::milliseconds(1200))).await.unwrap();
+        lazy.persistence.as_ref().unwrap().dirty.lock().await.insert("gone");
+        assert_eq!(1, lazy.flush().await.unwrap());
+        assert!(store.get(&"gone").await.unwrap().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_flushes_pending_snapshots() {
+        let store = Arc::new(InMemoryStore::new());
+        let registry = Arc::new(DetectorRegistry::new(DetectorConfig::default())
+            .with_store(store.clone(), WriteBackPolicy::Periodic(TimeDelta::hours(1))));
+        let token = CancellationToken::new();
+        registry.spawn_flusher(std::time::Duration::from_secs(3600), token.clone());
+        let detached = CancellationToken::new();
+        registry.spawn_flusher(std::time::Duration::from_secs(3600), detached.clone());
+        detached.cancel();
+
+        let start = Local::now();
+        registry.heartbeat("db", start).await.unwrap();
+        registry.heartbeat("db", start.add(Duration::milliseconds(100))).await.unwrap();
+        assert!(store.get(&"db").await.unwrap().unwrap().value.statistics.arrival_intervals.is_empty());
+
+        assert!(registry.shutdown().await);
+        assert!(registry.tasks.lock().unwrap().is_empty());
+        assert!(!token.is_cancelled());
+        let stored = store.get(&"db").await.unwrap().unwrap();
+        assert_eq!(vec![100], stored.value.statistics.arrival_intervals[..]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_scheduler_evaluates_every_node_once_per_tick() {
+        let registry = Arc::new(DetectorRegistry::new(DetectorConfig {
+            min_std_deviation: TimeDelta::milliseconds(20),
+            ..Default::default()
+        }));
+        let now = Local::now();
+        for node in 0..1000 {
+            // every hundredth node stopped a minute ago
+            let last = if node % 100 == 0 { now.sub(Duration::seconds(60)) } else { now };
+            for ms in [300, 200, 100, 0] {
+                registry.heartbeat(node, last.sub(Duration::milliseconds(ms))).await.unwrap();
+            }
+        }
+        let suspects = Arc::new(AtomicUsize::new(0));
+        let hook_suspects = Arc::clone(&suspects);
+        registry.on_suspect(move |_, _| {
+            let suspects = Arc::clone(&hook_suspects);
+            async move {
+                suspects.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        let token = CancellationToken::new();
+        let options = SchedulerOptions { interval: std::time::Duration::from_secs(1), workers: 4, filter: None };
+        let all = registry.spawn_scheduler(options, token.clone());
+        let even = registry.spawn_scheduler(SchedulerOptions {
+            filter: Some(Arc::new(|node: &i32| node % 2 == 0)),
+            ..Default::default()
+        }, token.clone());
+        tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+
+        let stats = all.stats();
+        assert_eq!(3, stats.ticks);
+        assert_eq!(1000, stats.evaluated);
+        assert_eq!(3000, stats.total_evaluated);
+        assert_eq!(4, stats.tasks);
+        assert_eq!(0, stats.overruns);
+        assert_eq!(500, even.stats().evaluated);
+        assert_eq!(10, suspects.load(Ordering::SeqCst));
+
+        token.cancel();
+        assert!(registry.shutdown().await);
+    }
+
+    #[tokio::test]
+    async fn test_reload_from_store() {
+        let store = Arc::new(InMemoryStore::new());
+        let registry = DetectorRegistry::new(DetectorConfig { threshold: 3., ..Default::default() })
+            .with_store(store.clone(), WriteBackPolicy::OnChange);
+        let start = Local::now();
+        for at in [0, 100, 220, 300] {
+            registry.heartbeat("db", start.add(Duration::milliseconds(at))).await.unwrap();
+        }
+
+        let restarted = DetectorRegistry::new(DetectorConfig::default())
+            .with_store(store.clone(), WriteBackPolicy::OnChange);
+        assert_eq!(1, restarted.reload().await.unwrap());
+        let t = start.add(Duration::milliseconds(450));
+        assert_eq!(registry.phi(&"db", t).await.unwrap(), restarted.phi(&"db", t).await.unwrap());
+        assert_eq!(3., restarted.get(&"db").await.unwrap().config().unwrap().threshold);
+        restarted.heartbeat("db", start.add(Duration::milliseconds(400))).await.unwrap();
+        assert_eq!(5, store.get(&"db").await.unwrap().unwrap().version);
+    }
+
+    #[tokio::test]
+    async fn test_write_back_version_conflict() {
+        let store = Arc::new(InMemoryStore::new());
+        let first = DetectorRegistry::new(DetectorConfig::default())
+            .with_store(store.clone(), WriteBackPolicy::OnChange);
+        let second = DetectorRegistry::new(DetectorConfig::default())
+            .with_store(store.clone(), WriteBackPolicy::OnChange);
+        let start = Local::now();
+        first.heartbeat("db", start).await.unwrap();
+        second.reload().await.unwrap();
+        first.heartbeat("db", start.add(Duration::milliseconds(100))).await.unwrap();
+
+        let err = second.heartbeat("db", start.add(Duration::milliseconds(110))).await.unwrap_err();
+        assert_eq!(Some(&StoreError::VersionConflict { expected: 1, actual: 2 }), err.downcast_ref::<StoreError>());
+        second.reload().await.unwrap();
+        second.heartbeat("db", start.add(Duration::milliseconds(200))).await.unwrap();
+        assert_eq!(3, store.get(&"db").await.unwrap().unwrap().version);
+    }
+
+    #[tokio::test]
+    async fn test_staleness_all() {
+        let registry = DetectorRegistry::new(DetectorConfig::default());
+        let start = Local::now();
+        registry.heartbeat("db", start).await.unwrap();
+        registry.heartbeat("db", start.add(Duration::milliseconds(100))).await.unwrap();
+        registry.get_or_create("idle").await;
+
+        let mut reports = registry.staleness_all(start.add(Duration::milliseconds(250))).await.unwrap();
+        reports.sort_by_key(|(key, _)| *key);
+        assert_eq!(Some(TimeDelta::milliseconds(150)), reports[0].1.elapsed);
+        assert_eq!(1, reports[0].1.samples);
+        assert_eq!(("idle", None), (reports[1].0, reports[1].1.elapsed));
+    }
+
+    #[tokio::test]
+    async fn test_suspected_with_severity() {
+        let registry = DetectorRegistry::new(DetectorConfig { min_std_deviation: TimeDelta::milliseconds(10), ..DetectorConfig::default() });
+        let start = Local::now();
+        for at in [0, 100, 200, 300] {
+            registry.heartbeat("db", start.add(Duration::milliseconds(at))).await.unwrap();
+            registry.heartbeat("cache", start.add(Duration::milliseconds(at + 200))).await.unwrap();
+        }
+        let t = start.add(Duration::milliseconds(600));
+        let suspected = registry.suspected_with_severity(t).await.unwrap();
+        assert_eq!(vec![("db", Severity { level: 3, name: "dead".to_string() })], suspected);
+        let mut severities = registry.severity_all(t).await.unwrap();
+        severities.sort_by_key(|(key, _)| *key);
+        assert_eq!(("cache", 0), (severities[0].0, severities[0].1.level));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_until_suspected_by_key() {
+        let registry = DetectorRegistry::new(DetectorConfig { min_std_deviation: TimeDelta::milliseconds(10), ..DetectorConfig::default() });
+        assert!(registry.wait_until_suspected(&"missing").await.unwrap().is_none());
+        let start = Local::now();
+        for ms in [0, 100, 200, 300] {
+            registry.heartbeat("db", start.add(Duration::milliseconds(ms))).await.unwrap();
+        }
+        let began = tokio::time::Instant::now();
+        let (phi, at) = registry.wait_until_suspected(&"db").await.unwrap().unwrap();
+        assert!(phi > crate::DEFAULT_THRESHOLD);
+        assert!(at > start.add(Duration::milliseconds(400)) && at < start.add(Duration::milliseconds(500)));
+        assert!(began.elapsed() > std::time::Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_evict_idle() {
+        let registry = DetectorRegistry::new(DetectorConfig::default());
+        let now = Local::now();
+        registry.heartbeat("gone", now.sub(Duration::minutes(30))).await.unwrap();
+        registry.heartbeat("active", now.sub(Duration::minutes(30))).await.unwrap();
+        registry.heartbeat("active", now).await.unwrap();
+        assert_eq!(vec!["gone"], registry.evict_idle(TimeDelta::minutes(10)).await.unwrap());
+        assert_eq!(vec!["active"], registry.keys().await);
+    }
+
+    #[tokio::test]
+    async fn test_evict_idle_keeps_node_that_heartbeats_meanwhile() {
+        let registry = Arc::new(DetectorRegistry::new(DetectorConfig::default()));
+        let now = Local::now();
+        registry.heartbeat("late", now.sub(Duration::minutes(30))).await.unwrap();
+        let detector = registry.get(&"late").await.unwrap();
+        let map = registry.detectors.read().await;
+        let evicting = tokio::spawn({
+            let registry = Arc::clone(&registry);
+            async move { registry.evict_idle(TimeDelta::minutes(10)).await.unwrap() }
+        });
+        // let the eviction find the node idle and queue for the map lock
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        detector.insert(now).await.unwrap();
+        drop(map);
+        assert!(evicting.await.unwrap().is_empty());
+        assert_eq!(vec!["late"], registry.keys().await);
+    }
+
+    #[tokio::test]
+    async fn test_clone_node_copies_statistics() {
+        let registry = DetectorRegistry::new(DetectorConfig::default());
+        let start = Local::now();
+        for at in [0, 100, 220] {
+            registry.heartbeat("primary", start.add(Duration::milliseconds(at))).await.unwrap();
+        }
+        assert!(registry.clone_node(&"missing", "replica").await.unwrap().is_none());
+        let replica = registry.clone_node(&"primary", "replica").await.unwrap().unwrap();
+        registry.heartbeat("replica", start.add(Duration::milliseconds(300))).await.unwrap();
+
+        let primary = registry.get(&"primary").await.unwrap();
+        assert_eq!(vec![100, 120], primary.statistics.read().await.arrival_intervals[..]);
+        assert_eq!(vec![100, 120, 80], replica.statistics.read().await.arrival_intervals[..]);
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_to_filtered_nodes() {
+        let registry = DetectorRegistry::new(DetectorConfig::default());
+        for key in ["db-1", "db-2", "batch-1"] {
+            registry.heartbeat(key, Local::now()).await.unwrap();
+        }
+        let config = DetectorConfig { threshold: 3.0, ..Default::default() };
+        assert_eq!(2, registry.apply_config_where(config.clone(), |key| key.starts_with("db")).await.unwrap());
+        assert_eq!(3.0, registry.get(&"db-2").await.unwrap().config().unwrap().threshold);
+        assert_eq!(crate::DEFAULT_THRESHOLD, registry.get(&"batch-1").await.unwrap().config().unwrap().threshold);
+
+        let invalid = DetectorConfig { window_length: 0, ..Default::default() };
+        assert!(registry.apply_config_all(invalid).await.is_err());
+        assert_eq!(3, registry.apply_config_all(config).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_sweep_invokes_hooks_once_per_transition() {
+        let config = DetectorConfig { min_std_deviation: TimeDelta::milliseconds(10), ..Default::default() };
+        let registry = DetectorRegistry::new(config);
+        let suspects = Arc::new(AtomicUsize::new(0));
+        let recoveries = Arc::new(AtomicUsize::new(0));
+        {
+            let suspects = Arc::clone(&suspects);
+            registry.on_suspect(move |key, _| {
+                let suspects = Arc::clone(&suspects);
+                async move {
+                    assert_eq!("b", key);
+                    suspects.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+        }
+        registry.on_suspect(|_, _| async { panic!("faulty hook") });
+        {
+            let recoveries = Arc::clone(&recoveries);
+            registry.on_recover(move |key, _| {
+                let recoveries = Arc::clone(&recoveries);
+                async move {
+                    assert_eq!("b", key);
+                    recoveries.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+        }
+
+        let start = Local::now();
+        for i in 0..10 {
+            for key in ["a", "b", "c"] {
+                registry.heartbeat(key, start.add(Duration::milliseconds(i * 100))).await.unwrap();
+            }
+        }
+        let mut t = start.add(Duration::milliseconds(900));
+        for _ in 0..10 {
+            t = t.add(Duration::milliseconds(100));
+            for key in ["a", "c"] {
+                registry.heartbeat(key, t).await.unwrap();
+            }
+            registry.sweep(t).await.unwrap();
+        }
+        assert_eq!(1, suspects.load(Ordering::SeqCst));
+        assert_eq!(0, recoveries.load(Ordering::SeqCst));
+
+        registry.heartbeat("b", t).await.unwrap();
+        let transitions = registry.sweep(t).await.unwrap();
+        assert!(matches!(transitions.as_slice(), [("b", Transition::Recovered(_))]));
+        assert!(registry.sweep(t).await.unwrap().is_empty());
+        assert_eq!(1, suspects.load(Ordering::SeqCst));
+        assert_eq!(1, recoveries.load(Ordering::SeqCst));
+    }
+
+    #[cfg(feature = "log")]
+    #[tokio::test]
+    async fn test_sweep_logs_only_transitions() {
+        use std::sync::Mutex;
+        use log::{Level, Log, Metadata, Record};
+
+        static RECORDS: Mutex<Vec<(Level, String, String)>> = Mutex::new(vec![]);
+        struct CapturingLogger;
+        impl Log for CapturingLogger {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+            fn log(&self, record: &Record) {
+                let node = record.key_values().get("node".into()).map(|v| v.to_string()).unwrap_or_default();
+                RECORDS.lock().unwrap().push((record.level(), record.target().to_string(), node));
+            }
+            fn flush(&self) {}
+        }
+        let _ = log::set_logger(&CapturingLogger);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let config = DetectorConfig { min_std_deviation: TimeDelta::milliseconds(10), ..Default::default() };
+        let registry = DetectorRegistry::new(config).with_log_target("sweep-test");
+        let start = Local::now();
+        for i in 0..10 {
+            registry.heartbeat("a", start.add(Duration::milliseconds(i * 100))).await.unwrap();
+        }
+        let mut t = start.add(Duration::milliseconds(900));
+        for _ in 0..5 {
+            t = t.add(Duration::milliseconds(100));
+            registry.sweep(t).await.unwrap();
+        }
+        registry.heartbeat("a", t).await.unwrap();
+        for _ in 0..3 {
+            registry.sweep(t).await.unwrap();
+        }
+        let records: Vec<_> = RECORDS.lock().unwrap().iter()
+            .filter(|(_, target, _)| target == "sweep-test")
+            .cloned()
+            .collect();
+        assert_eq!(vec![
+            (Level::Warn, "sweep-test".to_string(), "\"a\"".to_string()),
+            (Level::Info, "sweep-test".to_string(), "\"a\"".to_string()),
+        ], records);
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_are_isolated() {
+        let registry = NamespacedRegistry::new(DetectorConfig::default());
+        let acme = registry.namespace("acme").await;
+        let globex = registry.create_namespace("globex", DetectorConfig { threshold: 0.5, ..Default::default() }).await;
+
+        let start = Local::now();
+        for i in 0..5 {
+            acme.heartbeat("node-1", start.add(Duration::milliseconds(i * 100))).await.unwrap();
+            globex.heartbeat("node-1", start.add(Duration::milliseconds(i * 1000))).await.unwrap();
+        }
+        let acme_node = acme.get(&"node-1").await.unwrap();
+        let globex_node = globex.get(&"node-1").await.unwrap();
+        assert_eq!(vec![100; 4], acme_node.statistics.read().await.arrival_intervals[..]);
+        assert_eq!(vec![1000; 4], globex_node.statistics.read().await.arrival_intervals[..]);
+        assert_eq!(0.5, globex_node.config().unwrap().threshold);
+
+        assert!(registry.drop_namespace(&"globex").await);
+        assert!(!registry.drop_namespace(&"globex").await);
+        assert_eq!(vec!["acme"], registry.namespaces().await);
+        assert!(registry.namespace("globex").await.get(&"node-1").await.is_none());
+        assert!(registry.namespace("acme").await.get(&"node-1").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_namespace_sweeps_with_its_own_hooks_and_store() {
+        let registry = NamespacedRegistry::new(DetectorConfig::default());
+        let store = Arc::new(InMemoryStore::new());
+        let strict = DetectorConfig { threshold: 1.0, min_std_deviation: TimeDelta::milliseconds(10), ..Default::default() };
+        let acme = registry.insert_namespace("acme", DetectorRegistry::new(DetectorConfig::default())
+            .with_store(store.clone(), WriteBackPolicy::OnChange)).await;
+        let globex = registry.namespace("globex").await;
+        let suspects = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&suspects);
+        acme.registry().on_suspect(move |_, _| {
+            let counter = Arc::clone(&counter);
+            async move { counter.fetch_add(1, Ordering::SeqCst); }
+        });
+
+        let start = Local::now();
+        for handle in [&acme, &globex] {
+            handle.set_config("node-1", strict.clone()).await.unwrap();
+            for i in 0..5 {
+                handle.heartbeat("node-1", start.add(Duration::milliseconds(i * 100))).await.unwrap();
+            }
+        }
+        let t = start.add(Duration::seconds(5));
+        assert_eq!(1, acme.sweep(t).await.unwrap().len());
+        assert_eq!(1, suspects.load(Ordering::SeqCst));
+        assert_eq!(1, globex.sweep(t).await.unwrap().len());
+        assert_eq!(1, suspects.load(Ordering::SeqCst));
+        assert_eq!(5, store.get(&"node-1").await.unwrap().unwrap().version);
+        assert!(Arc::ptr_eq(acme.registry(), registry.namespace("acme").await.registry()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drop_namespace_shuts_down_its_registry() {
+        let registry = NamespacedRegistry::new(DetectorConfig::default());
+        let store = Arc::new(InMemoryStore::new());
+        let acme = registry.insert_namespace("acme", DetectorRegistry::new(DetectorConfig::default())
+            .with_store(store.clone(), WriteBackPolicy::Periodic(TimeDelta::hours(1)))).await;
+        acme.registry().spawn_flusher(std::time::Duration::from_secs(3600), CancellationToken::new());
+        let start = Local::now();
+        acme.heartbeat("node-1", start).await.unwrap();
+        acme.heartbeat("node-1", start.add(Duration::milliseconds(100))).await.unwrap();
+        let finished = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&finished);
+        acme.get(&"node-1").await.unwrap().on_shutdown(move |summary| {
+            counter.fetch_add(summary.heartbeats as usize, Ordering::SeqCst);
+        });
+
+        assert!(registry.drop_namespace(&"acme").await);
+        assert_eq!(2, finished.load(Ordering::SeqCst));
+        assert!(acme.registry().tasks.lock().unwrap().is_empty());
+        assert!(acme.registry().is_empty().await);
+        let stored = store.get(&"node-1").await.unwrap().unwrap();
+        assert_eq!(vec![100], stored.value.statistics.arrival_intervals[..]);
+    }
+
+    #[tokio::test]
+    async fn test_health_score_weighting_and_warming() {
+        let config = DetectorConfig { min_samples: 3, threshold: 1.0, ..Default::default() };
+        let registry = DetectorRegistry::new(config.clone());
+        let start = Local::now();
+        for (healthy_at, dead_at) in [(0, 0), (90, 10), (210, 22), (300, 30), (400, 42)] {
+            registry.heartbeat("healthy", start.add(Duration::milliseconds(healthy_at))).await.unwrap();
+            registry.heartbeat("dead", start.add(Duration::milliseconds(dead_at))).await.unwrap();
+        }
+        registry.heartbeat("warming", start).await.unwrap();
+        registry.get_or_create("idle").await;
+
+        let t = start.add(Duration::milliseconds(450));
+        let health = registry.health_score(t, &HealthScoreOptions::default()).await.unwrap();
+        assert_eq!((1, 1, 1, 1), (health.healthy, health.warming, health.suspected, health.idle));
+        assert!((health.score - 200. / 3.).abs() < 1e-9);
+
+        let mut opts = HealthScoreOptions { warming_as_healthy: false, ..Default::default() };
+        opts.weights.insert("dead", 3.);
+        let health = registry.health_score(t, &opts).await.unwrap();
+        assert_eq!(25., health.score);
+    }
+
+    #[tokio::test]
+    async fn test_rank_orders_by_phi_with_stable_ties() {
+        let registry = DetectorRegistry::new(DetectorConfig { min_samples: 2, ..Default::default() });
+        let start = Local::now();
+        for (a, b, c) in [(0, 0, 0), (90, 90, 10), (210, 210, 22), (300, 300, 30)] {
+            registry.heartbeat("a", start.add(Duration::milliseconds(a))).await.unwrap();
+            registry.heartbeat("b", start.add(Duration::milliseconds(b))).await.unwrap();
+            registry.heartbeat("c", start.add(Duration::milliseconds(c))).await.unwrap();
+        }
+        registry.heartbeat("warming", start).await.unwrap();
+        let t = start.add(Duration::milliseconds(380));
+
+        let ranked = registry.rank(&["c", "warming", "unknown", "b", "a"], t).await.unwrap();
+        let keys: Vec<_> = ranked.iter().map(|(key, _)| *key).collect();
+        assert_eq!(vec!["b", "a", "c", "warming", "unknown"], keys);
+        assert_eq!(ranked[0].1, ranked[1].1);
+        assert_eq!(f64::INFINITY, ranked[4].1);
+
+        let ranked = registry.rank_with(&["a", "unknown", "b"], t, UnrankedPolicy::Exclude).await.unwrap();
+        let keys: Vec<_> = ranked.iter().map(|(key, _)| *key).collect();
+        assert_eq!(vec!["a", "b"], keys);
+        assert_eq!(Some("b"), registry.best(&["c", "b", "a"], t).await.unwrap());
+        assert_eq!(Some("warming"), registry.best(&["unknown", "warming"], t).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_rank_reuses_phi_computed_for_t() {
+        let registry = DetectorRegistry::new(DetectorConfig { min_std_deviation: TimeDelta::milliseconds(10), ..Default::default() });
+        let start = Local::now();
+        for at in [0, 100, 200, 300] {
+            registry.heartbeat("a", start.add(Duration::milliseconds(at))).await.unwrap();
+        }
+        let t = start.add(Duration::milliseconds(400));
+        let detector = registry.get(&"a").await.unwrap();
+        let phi = detector.phi(t).await.unwrap();
+        // moved behind the Detector's back, so a recomputed phi would differ
+        detector.statistics.write().await.last_arrived_at = t;
+        assert_eq!(vec![("a", phi)], registry.rank(&["a"], t).await.unwrap());
+        registry.heartbeat("a", t).await.unwrap();
+        assert_eq!(vec![("a", 0.)], registry.rank(&["a"], t).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_all_streams_every_node() {
+        let registry = Arc::new(DetectorRegistry::new(DetectorConfig { min_std_deviation: TimeDelta::milliseconds(10), ..DetectorConfig::default() }));
+        let start = Local::now();
+        for at in [0, 100, 200, 300] {
+            for key in ["db", "cache", "queue"] {
+                registry.heartbeat(key, start.add(Duration::milliseconds(at))).await.unwrap();
+            }
+        }
+        let t = start.add(Duration::milliseconds(350));
+        let mut evaluations: Vec<_> = Arc::clone(&registry).evaluate_all(t, 8.).collect().await;
+        evaluations.sort_by_key(|(key, _)| *key);
+        let keys: Vec<_> = evaluations.iter().map(|(key, _)| *key).collect();
+        assert_eq!(vec!["cache", "db", "queue"], keys);
+        let expected = registry.get(&"db").await.unwrap().evaluate(t, 8.).await.unwrap();
+        assert!(evaluations.iter().all(|(_, evaluation)| *evaluation == expected));
+    }
+
+    #[tokio::test]
+    async fn test_remove_runs_shutdown_callbacks() {
+        let registry = DetectorRegistry::new(DetectorConfig::default());
+        registry.heartbeat("leaving", Local::now()).await.unwrap();
+        let heartbeats = Arc::new(AtomicUsize::new(0));
+        let seen = Arc::clone(&heartbeats);
+        registry.get(&"leaving").await.unwrap().on_shutdown(move |summary| seen.store(summary.heartbeats as usize, Ordering::SeqCst));
+        assert!(registry.remove(&"leaving").await.is_some());
+        assert_eq!(1, heartbeats.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,76 @@
+use crate::PhiError;
+
+/// Named bands phi is mapped into, e.g. healthy, degraded, suspected and dead.
+/// Band i is entered once phi exceeds its lower bound, matching how `threshold` is compared.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeverityBands {
+    base: String,
+    bands: Vec<(f64, String)>,
+}
+
+/// Band phi falls into
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Severity {
+    /// 0 for the base band, increasing by one per band.
+    pub level: usize,
+    pub name: String,
+}
+
+impl SeverityBands {
+    /// Bands starting with base for low phi, followed by (lower bound, name) pairs whose
+    /// bounds must be finite, not negative and strictly increasing.
+    pub fn new(base: impl Into<String>, bands: &[(f64, &str)]) -> Result<Self, PhiError> {
+        let invalid = |reason: &str| Err(PhiError::InvalidConfig(reason.to_string()));
+        if bands.iter().any(|(bound, _)| !bound.is_finite() || *bound < 0.) {
+            return invalid("severity band bounds must be finite and not negative");
+        }
+        if bands.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return invalid("severity band bounds must be strictly increasing");
+        }
+        Ok(SeverityBands {
+            base: base.into(),
+            bands: bands.iter().map(|(bound, name)| (*bound, name.to_string())).collect(),
+        })
+    }
+
+    /// Band of phi.
+    pub fn classify(&self, phi: f64) -> Severity {
+        let level = self.bands.iter().take_while(|(bound, _)| phi > *bound).count();
+        let name = match level {
+            0 => self.base.clone(),
+            level => self.bands[level - 1].1.clone(),
+        };
+        Severity { level, name }
+    }
+}
+
+impl Default for SeverityBands {
+    /// Healthy up to phi 1, degraded up to 5, suspected up to 8 and dead above.
+    fn default() -> Self {
+        SeverityBands {
+            base: "healthy".to_string(),
+            bands: vec![(1., "degraded".to_string()), (5., "suspected".to_string()), (8., "dead".to_string())],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PhiError, SeverityBands};
+
+    #[test]
+    fn test_default_bands_at_boundaries() {
+        let bands = SeverityBands::default();
+        let levels: Vec<_> = [0., 1., 1.01, 5., 5.01, 8., 8.01, 100.].iter().map(|phi| bands.classify(*phi).level).collect();
+        assert_eq!(vec![0, 0, 1, 1, 2, 2, 3, 3], levels);
+        assert_eq!("healthy", bands.classify(0.5).name);
+        assert_eq!("dead", bands.classify(9.).name);
+    }
+
+    #[test]
+    fn test_bands_must_increase() {
+        assert!(matches!(SeverityBands::new("ok", &[(2., "bad"), (2., "worse")]), Err(PhiError::InvalidConfig(_))));
+        assert!(matches!(SeverityBands::new("ok", &[(f64::NAN, "bad")]), Err(PhiError::InvalidConfig(_))));
+        assert_eq!(1, SeverityBands::new("ok", &[(3., "bad")]).unwrap().classify(4.).level);
+    }
+}
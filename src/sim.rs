@@ -0,0 +1,183 @@
+use chrono::{DateTime, Local, TimeDelta};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::{DetectorConfig, Statistics};
+
+/// Distribution synthetic heartbeat intervals are drawn from, in ms
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimDistribution {
+    /// Normal intervals, negative draws clamped to 0.
+    Normal { mean: f64, std_dev: f64 },
+    Exponential { mean: f64 },
+}
+
+/// Outage replacing the interval after heartbeat `after_heartbeat` with `length_ms` of silence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Outage {
+    pub after_heartbeat: u32,
+    pub length_ms: u64,
+}
+
+/// Synthetic heartbeat stream for `simulate_stream`
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    pub detector: DetectorConfig,
+    pub distribution: SimDistribution,
+    /// Number of heartbeats to generate.
+    pub heartbeats: u32,
+    pub outages: Vec<Outage>,
+    pub threshold: f64,
+    /// Spacing of the phi samples taken between heartbeats, at least 1 ms.
+    pub sample_every_ms: u64,
+    /// Seed of the interval generator, so that runs are reproducible.
+    pub seed: u64,
+}
+
+/// Phi statistics of a simulated stream
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimResult {
+    /// Number of phi samples taken outside outages.
+    pub samples: u64,
+    /// Samples outside outages whose phi exceeded the threshold.
+    pub false_positives: u64,
+    pub false_positive_rate: f64,
+    pub mean_phi: f64,
+    pub max_phi: f64,
+    /// For each detected outage, ms from its last heartbeat until phi exceeded the threshold.
+    pub detection_latencies_ms: Vec<u64>,
+    /// Outages during which phi never exceeded the threshold.
+    pub missed_outages: u32,
+}
+
+impl SimResult {
+    /// Mean of the detection latencies, None if no outage was detected.
+    pub fn mean_detection_latency_ms(&self) -> Option<f64> {
+        let detected = self.detection_latencies_ms.len();
+        (detected != 0).then(|| self.detection_latencies_ms.iter().sum::<u64>() as f64 / detected as f64)
+    }
+}
+
+/// Generate the configured heartbeat stream, feed it through a window configured with
+/// config.detector and sample phi every sample_every_ms between heartbeats. Phi is only
+/// sampled once the window holds an interval.
+pub fn simulate_stream(config: SimConfig) -> SimResult {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let step = config.sample_every_ms.max(1);
+    let start: DateTime<Local> = DateTime::UNIX_EPOCH.into();
+    let mut stats = Statistics::starting_at(config.detector.window_length, start);
+    stats.configure(&config.detector);
+    let mut result = SimResult {
+        samples: 0,
+        false_positives: 0,
+        false_positive_rate: 0.,
+        mean_phi: 0.,
+        max_phi: 0.,
+        detection_latencies_ms: vec![],
+        missed_outages: 0,
+    };
+    let mut phi_sum = 0.;
+    let mut at = start;
+    for heartbeat in 0..config.heartbeats {
+        stats.insert(at);
+        if heartbeat + 1 == config.heartbeats {
+            break;
+        }
+        let outage = config.outages.iter().find(|outage| outage.after_heartbeat == heartbeat);
+        let gap = match outage {
+            Some(outage) => outage.length_ms,
+            None => draw_interval(&mut rng, config.distribution),
+        };
+        let sampled = !stats.arrival_intervals.is_empty();
+        let mut detected = None;
+        let mut offset = step;
+        while sampled && offset < gap {
+            let phi = stats.phi_at(at + TimeDelta::milliseconds(offset as i64), &config.detector);
+            if outage.is_some() {
+                if phi > config.threshold {
+                    detected = Some(offset);
+                    break;
+                }
+            } else {
+                result.samples += 1;
+                phi_sum += phi;
+                result.max_phi = result.max_phi.max(phi);
+                if phi > config.threshold {
+                    result.false_positives += 1;
+                }
+            }
+            offset += step;
+        }
+        if outage.is_some() {
+            match detected {
+                Some(latency) => result.detection_latencies_ms.push(latency),
+                None => result.missed_outages += 1,
+            }
+        }
+        at += TimeDelta::milliseconds(gap as i64);
+    }
+    if result.samples != 0 {
+        result.mean_phi = phi_sum / result.samples as f64;
+        result.false_positive_rate = result.false_positives as f64 / result.samples as f64;
+    }
+    result
+}
+
+/// Interval in whole ms drawn from distribution.
+fn draw_interval(rng: &mut StdRng, distribution: SimDistribution) -> u64 {
+    // in (0, 1], so that its logarithm is finite
+    let u = 1. - rng.gen::<f64>();
+    let ms = match distribution {
+        SimDistribution::Normal { mean, std_dev } => {
+            // Box-Muller
+            let v = rng.gen::<f64>();
+            mean + std_dev * (-2. * u.ln()).sqrt() * (2. * std::f64::consts::PI * v).cos()
+        }
+        SimDistribution::Exponential { mean } => -mean * u.ln(),
+    };
+    ms.round().max(0.) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use crate::{simulate_stream, DetectorConfig, Outage, SimConfig, SimDistribution};
+
+    fn config(distribution: SimDistribution) -> SimConfig {
+        SimConfig {
+            detector: DetectorConfig { window_length: 50, min_std_deviation: TimeDelta::milliseconds(10), ..DetectorConfig::default() },
+            distribution,
+            heartbeats: 200,
+            outages: vec![Outage { after_heartbeat: 100, length_ms: 5000 }, Outage { after_heartbeat: 150, length_ms: 5000 }],
+            threshold: 8.,
+            sample_every_ms: 10,
+            seed: 7,
+        }
+    }
+
+    #[test]
+    fn test_simulated_normal_stream() {
+        let result = simulate_stream(config(SimDistribution::Normal { mean: 100., std_dev: 10. }));
+        assert!(result.samples > 1000);
+        assert!(result.false_positive_rate < 0.01);
+        assert!(result.mean_phi > 0. && result.mean_phi < result.max_phi);
+        assert_eq!(0, result.missed_outages);
+        assert_eq!(2, result.detection_latencies_ms.len());
+        let latencies = &result.detection_latencies_ms;
+        assert!(latencies[0] > 100 && latencies[0] < 1000);
+        // the first outage is still in the window, inflating the variance
+        assert!(latencies[1] > latencies[0]);
+        assert_eq!(result, simulate_stream(config(SimDistribution::Normal { mean: 100., std_dev: 10. })));
+    }
+
+    #[test]
+    fn test_simulated_exponential_stream() {
+        let mut exponential = config(SimDistribution::Exponential { mean: 100. });
+        exponential.outages.clear();
+        exponential.threshold = 3.;
+        let result = simulate_stream(exponential);
+        assert!(result.samples > 1000);
+        assert!(result.false_positives > 0);
+        assert_eq!(None, result.mean_detection_latency_ms());
+        assert_eq!(0, result.missed_outages);
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::hash::Hash;
+use std::sync::Arc;
+use async_trait::async_trait;
+use chrono::{DateTime, Local, TimeDelta};
+use tokio::sync::Mutex;
+use crate::{Detector, DetectorConfig, Statistics, StoreError};
+
+/// Persisted state of a single node
+#[derive(Clone, Debug)]
+pub struct NodeSnapshot {
+    pub config: DetectorConfig,
+    pub statistics: Statistics,
+}
+
+impl NodeSnapshot {
+    /// Detector resuming from this snapshot.
+    pub fn into_detector(self) -> Detector {
+        Detector::from_statistics(self.config, self.statistics)
+    }
+}
+
+/// Value stored with the version it was written at
+#[derive(Clone, Debug)]
+pub struct Versioned<T> {
+    /// Starts at 1 on the first write and increases by one on every write.
+    pub version: u64,
+    pub value: T,
+}
+
+/// External store of node snapshots shared by registries, with optimistic versioning.
+/// A put only succeeds when expected_version matches the stored version, 0 for absent keys.
+#[async_trait]
+pub trait NodeStore<K: Send + Sync>: Send + Sync {
+    /// Snapshot stored for key, if any.
+    async fn get(&self, key: &K) -> Result<Option<Versioned<NodeSnapshot>>, StoreError>;
+
+    /// Store snapshot for key if its stored version is still expected_version, returning the new version.
+    async fn put(&self, key: K, snapshot: NodeSnapshot, expected_version: u64) -> Result<u64, StoreError>;
+
+    /// Remove the snapshot of key. Returns whether it existed.
+    async fn remove(&self, key: &K) -> Result<bool, StoreError>;
+
+    /// Keys with a stored snapshot.
+    async fn list(&self) -> Result<Vec<K>, StoreError>;
+}
+
+/// External store of the statistics of single detectors, e.g. backed by Redis, sled or a
+/// file, for checkpointing a Detector without the versioning of `NodeStore`.
+#[async_trait]
+pub trait StateStore<K: Send + Sync>: Send + Sync {
+    /// Store statistics for key, replacing any stored before.
+    async fn save(&self, key: &K, statistics: &Statistics) -> Result<(), StoreError>;
+
+    /// Statistics stored for key, if any.
+    async fn load(&self, key: &K) -> Result<Option<Statistics>, StoreError>;
+}
+
+impl Detector {
+    /// Save a copy of the statistics to store under key. The copy is taken first, so the
+    /// statistics are not locked while the store writes.
+    pub async fn checkpoint_to<K: Send + Sync>(&self, store: &dyn StateStore<K>, key: &K) -> Result<(), Box<dyn Error>> {
+        let statistics = self.statistics.read().await.clone();
+        Ok(store.save(key, &statistics).await?)
+    }
+
+    /// Replace the statistics with the ones stored under key, fitted to the current window_length
+    /// and configuration. Returns false, leaving the Detector untouched, if none are stored.
+    pub async fn restore_from<K: Send + Sync>(&self, store: &dyn StateStore<K>, key: &K) -> Result<bool, Box<dyn Error>> {
+        let Some(statistics) = store.load(key).await? else {
+            return Ok(false);
+        };
+        let config = self.read_config()?;
+        let mut restored = statistics.with_window_length(config.window_length);
+        restored.configure(&config);
+        let mut stats = self.statistics.write().await;
+        *stats = restored;
+        *self.smoothed_phi.lock().await = None;
+        self.notify_changed();
+        Ok(true)
+    }
+}
+
+/// NodeStore keeping snapshots in process memory
+#[derive(Debug, Default)]
+pub struct InMemoryStore<K> {
+    snapshots: Mutex<HashMap<K, Versioned<NodeSnapshot>>>,
+}
+
+impl<K> InMemoryStore<K> {
+    /// New empty store.
+    pub fn new() -> Self {
+        InMemoryStore { snapshots: Mutex::new(HashMap::new()) }
+    }
+}
+
+#[async_trait]
+impl<K> NodeStore<K> for InMemoryStore<K>
+    where K: Eq + Hash + Clone + Send + Sync {
+    async fn get(&self, key: &K) -> Result<Option<Versioned<NodeSnapshot>>, StoreError> {
+        Ok(self.snapshots.lock().await.get(key).cloned())
+    }
+
+    async fn put(&self, key: K, snapshot: NodeSnapshot, expected_version: u64) -> Result<u64, StoreError> {
+        let mut snapshots = self.snapshots.lock().await;
+        let actual = snapshots.get(&key).map_or(0, |stored| stored.version);
+        if actual != expected_version {
+            return Err(StoreError::VersionConflict { expected: expected_version, actual });
+        }
+        snapshots.insert(key, Versioned { version: actual + 1, value: snapshot });
+        Ok(actual + 1)
+    }
+
+    async fn remove(&self, key: &K) -> Result<bool, StoreError> {
+        Ok(self.snapshots.lock().await.remove(key).is_some())
+    }
+
+    async fn list(&self) -> Result<Vec<K>, StoreError> {
+        Ok(self.snapshots.lock().await.keys().cloned().collect())
+    }
+}
+
+/// When a registry writes changed nodes back to its store
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteBackPolicy {
+    /// Write a node on every heartbeat it receives.
+    OnChange,
+    /// Write all changed nodes once a heartbeat arrives at least this long after the previous
+    /// write-back, or on `DetectorRegistry::flush`.
+    Periodic(TimeDelta),
+}
+
+/// Store of a registry together with its write-back bookkeeping
+pub(crate) struct Persistence<K> {
+    pub(crate) store: Arc<dyn NodeStore<K>>,
+    pub(crate) policy: WriteBackPolicy,
+    /// Version of every key as last read from or written to the store.
+    pub(crate) versions: Mutex<HashMap<K, u64>>,
+    pub(crate) dirty: Mutex<HashSet<K>>,
+    pub(crate) last_flush: Mutex<Option<DateTime<Local>>>,
+}
+
+impl<K> Persistence<K> {
+    pub(crate) fn new(store: Arc<dyn NodeStore<K>>, policy: WriteBackPolicy) -> Self {
+        Persistence {
+            store,
+            policy,
+            versions: Mutex::new(HashMap::new()),
+            dirty: Mutex::new(HashSet::new()),
+            last_flush: Mutex::new(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::ops::Add;
+    use async_trait::async_trait;
+    use chrono::{Duration, Local, TimeDelta};
+    use tokio::sync::Mutex;
+    use crate::{DetectorBuilder, DetectorConfig, InMemoryStore, NodeSnapshot, NodeStore, PhiInteraction, StateStore, Statistics, StoreError};
+
+    fn snapshot() -> NodeSnapshot {
+        NodeSnapshot { config: DetectorConfig::default(), statistics: Statistics::new(10) }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store_versions() {
+        let store = InMemoryStore::new();
+        assert_eq!(1, store.put("db", snapshot(), 0).await.unwrap());
+        assert_eq!(2, store.put("db", snapshot(), 1).await.unwrap());
+        assert_eq!(Err(StoreError::VersionConflict { expected: 1, actual: 2 }), store.put("db", snapshot(), 1).await);
+        assert_eq!(Err(StoreError::VersionConflict { expected: 0, actual: 2 }), store.put("db", snapshot(), 0).await);
+        assert_eq!(2, store.get(&"db").await.unwrap().unwrap().version);
+
+        let mut statistics = Statistics::new(10);
+        statistics.insert(Local::now());
+        assert_eq!(1, store.put("cache", NodeSnapshot { statistics, ..snapshot() }, 0).await.unwrap());
+        let mut keys = store.list().await.unwrap();
+        keys.sort();
+        assert_eq!(vec!["cache", "db"], keys);
+        assert!(store.remove(&"db").await.unwrap());
+        assert!(!store.remove(&"db").await.unwrap());
+        assert!(store.get(&"db").await.unwrap().is_none());
+    }
+
+    #[derive(Default)]
+    struct HashMapStateStore(Mutex<HashMap<String, Statistics>>);
+
+    #[async_trait]
+    impl StateStore<String> for HashMapStateStore {
+        async fn save(&self, key: &String, statistics: &Statistics) -> Result<(), StoreError> {
+            self.0.lock().await.insert(key.clone(), statistics.clone());
+            Ok(())
+        }
+
+        async fn load(&self, key: &String) -> Result<Option<Statistics>, StoreError> {
+            Ok(self.0.lock().await.get(key).cloned())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_state_store_round_trip() {
+        let store = HashMapStateStore::default();
+        let builder = || DetectorBuilder::new().min_std_deviation(TimeDelta::milliseconds(10));
+        let detector = builder().build();
+        let key = "db-1".to_string();
+        let mut last = Local::now();
+        for ms in [100, 120, 90, 110] {
+            last = detector.insert_after_ms(ms).await.unwrap();
+        }
+        detector.checkpoint_to(&store, &key).await.unwrap();
+
+        let restored = builder().build();
+        assert!(!restored.restore_from(&store, &"db-2".to_string()).await.unwrap());
+        assert!(restored.restore_from(&store, &key).await.unwrap());
+        assert_eq!(last, restored.last_arrived_at().await.unwrap());
+        let t = last.add(Duration::milliseconds(150));
+        assert_eq!(detector.phi(t).await.unwrap(), restored.phi(t).await.unwrap());
+    }
+}
@@ -0,0 +1,65 @@
+use std::error::Error;
+use chrono::{DateTime, Local};
+use crate::Detector;
+
+impl Detector {
+    /// Record phi at t through the `metrics` facade as `phi_accrual_phi`, with its severity band
+    /// level as `phi_accrual_severity_level`, along with the window's `phi_accrual_mean_ms`,
+    /// `phi_accrual_std_dev_ms` and `phi_accrual_samples` gauges and the
+    /// `phi_accrual_heartbeats_total` counter of recorded heartbeats, all tagged with labels.
+    /// Mean and standard deviation are left out while the window is empty.
+    pub async fn emit_metrics(&self, t: DateTime<Local>, labels: &[(&'static str, String)]) -> Result<(), Box<dyn Error>> {
+        let config = self.read_config()?;
+        let stats = self.statistics.read().await;
+        let phi = if stats.n != 0 { self.phi_of(&stats, t, &config) } else { 0. };
+        metrics::gauge!("phi_accrual_phi", labels).set(phi);
+        metrics::gauge!("phi_accrual_severity_level", labels).set(config.severity_bands.classify(phi).level as f64);
+        if !stats.arrival_intervals.is_empty() {
+            let (variance, mean) = stats.moments(config.variance_reference);
+            metrics::gauge!("phi_accrual_mean_ms", labels).set(mean);
+            metrics::gauge!("phi_accrual_std_dev_ms", labels).set(variance.sqrt());
+        }
+        metrics::gauge!("phi_accrual_samples", labels).set(stats.arrival_intervals.len() as f64);
+        metrics::counter!("phi_accrual_heartbeats_total", labels).absolute(stats.heartbeats_recorded());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::ops::Add;
+    use chrono::{Duration, Local};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use crate::{Detector, PhiInteraction};
+
+    #[test]
+    fn test_emit_metrics_names() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let detector = Detector::new(10);
+        let start = Local::now();
+        metrics::with_local_recorder(&recorder, || runtime.block_on(async {
+            for ms in [0, 100, 300] {
+                detector.insert(start.add(Duration::milliseconds(ms))).await.unwrap();
+            }
+            detector.emit_metrics(start.add(Duration::milliseconds(600)), &[("node", "a".to_string())]).await.unwrap();
+        }));
+        let emitted: HashMap<_, _> = snapshotter.snapshot().into_vec().into_iter()
+            .map(|(key, _, _, value)| {
+                let labels: Vec<_> = key.key().labels().map(|label| (label.key().to_string(), label.value().to_string())).collect();
+                assert_eq!(vec![("node".to_string(), "a".to_string())], labels);
+                (key.key().name().to_string(), value)
+            })
+            .collect();
+        assert_eq!(6, emitted.len());
+        // three standard deviations past the mean, degraded under the default bands
+        assert!(matches!(emitted["phi_accrual_phi"], DebugValue::Gauge(phi) if phi.0 > 1. && phi.0 < 5.));
+        assert_eq!(DebugValue::Gauge(1.0.into()), emitted["phi_accrual_severity_level"]);
+        assert_eq!(DebugValue::Gauge(150.0.into()), emitted["phi_accrual_mean_ms"]);
+        assert_eq!(DebugValue::Gauge(50.0.into()), emitted["phi_accrual_std_dev_ms"]);
+        assert_eq!(DebugValue::Gauge(2.0.into()), emitted["phi_accrual_samples"]);
+        assert_eq!(DebugValue::Counter(3), emitted["phi_accrual_heartbeats_total"]);
+    }
+}
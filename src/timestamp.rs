@@ -0,0 +1,67 @@
+use std::time::{Instant, SystemTime};
+use chrono::{DateTime, TimeZone};
+
+/// Point in time heartbeats are recorded at, so that `Statistics` can run on any time source
+/// such as `Instant`, `SystemTime`, `DateTime` or a custom monotonic counter.
+pub trait Timestamp: Copy {
+    /// Milliseconds elapsed from other to self, negative if self is earlier.
+    fn delta_ms_since(&self, other: &Self) -> i64;
+}
+
+impl<Tz: TimeZone + Copy> Timestamp for DateTime<Tz>
+    where Tz::Offset: Copy {
+    fn delta_ms_since(&self, other: &Self) -> i64 {
+        self.signed_duration_since(*other).num_milliseconds()
+    }
+}
+
+impl Timestamp for Instant {
+    fn delta_ms_since(&self, other: &Self) -> i64 {
+        match self.checked_duration_since(*other) {
+            Some(elapsed) => i64::try_from(elapsed.as_millis()).unwrap_or(i64::MAX),
+            None => -i64::try_from(other.duration_since(*self).as_millis()).unwrap_or(i64::MAX),
+        }
+    }
+}
+
+impl Timestamp for SystemTime {
+    fn delta_ms_since(&self, other: &Self) -> i64 {
+        match self.duration_since(*other) {
+            Ok(elapsed) => i64::try_from(elapsed.as_millis()).unwrap_or(i64::MAX),
+            Err(e) => -i64::try_from(e.duration().as_millis()).unwrap_or(i64::MAX),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Add;
+    use chrono::{Duration, Local, TimeDelta};
+    use crate::{DetectorConfig, Statistics, Timestamp};
+
+    /// Monotonic counter ticking once per millisecond
+    #[derive(Clone, Copy, Debug)]
+    struct Tick(i64);
+
+    impl Timestamp for Tick {
+        fn delta_ms_since(&self, other: &Self) -> i64 {
+            self.0 - other.0
+        }
+    }
+
+    #[test]
+    fn test_statistics_over_counter_timestamps() {
+        let config = DetectorConfig { min_std_deviation: TimeDelta::milliseconds(10), ..DetectorConfig::default() };
+        let mut ticks = Statistics::starting_at(10, Tick(0));
+        let mut dates = Statistics::new(10);
+        let start = Local::now();
+        for ms in [0, 100, 180, 300, 390] {
+            ticks.insert(Tick(ms));
+            dates.insert(start.add(Duration::milliseconds(ms)));
+        }
+        assert_eq!(97.5, ticks.mean());
+        for ms in [400, 450, 600] {
+            assert_eq!(dates.phi_at(start.add(Duration::milliseconds(ms)), &config), ticks.phi_at(Tick(ms), &config));
+        }
+    }
+}